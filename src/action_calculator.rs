@@ -6,6 +6,7 @@ use bjaction::BJAction::STAND;
 use bjaction::BJAction::DOUBLE;
 use bjaction::BJAction::SURRENDER;
 use bjaction::BJAction::SPLIT;
+use bjaction::BJ_ACTIONS;
 use cards::card::Card;
 use cards::value::VALUES;
 use cards::value::ACE;
@@ -26,14 +27,55 @@ use hand_hasher::DealerHandHasher;
 use hand_hasher::SuitlessDeckHasher;
 use hash_database::InMemoryHashDatabase;
 use hand::score_for_value;
+use hand::SCORE_VALUES;
+use outcome::OutcomeDistribution;
+use hole_card::HoleCardHint;
+use payoff::Payoff;
+use bank::MultipleHands;
+use std::collections::HashMap;
 use self::scope_time::TimeIt;
 
+/**
+  How much of the cards the player's decisions get to see.  The dealer's up
+  card and the rules are always known.
+  */
+#[derive(Copy, Clone, PartialEq, Show)]
+pub enum StrategyMode {
+    // One decision per hand total and up card, like published basic strategy
+    TotalDependent,
+    // The exact cards for two card hands, the total after that
+    TwoCardCompositionDependent,
+    // The exact cards of the hand and the shoe at every decision
+    CompositionDependent,
+}
+
+pub const STRATEGY_MODES: [StrategyMode; 3] = [StrategyMode::TotalDependent,
+    StrategyMode::TwoCardCompositionDependent, StrategyMode::CompositionDependent];
+
 pub struct ActionCalculator<'a, 'b> {
     player_hand_hasher: Box<HandHasher + 'a>,
     dealer_hand_hasher: Box<HandHasher + 'a>,
     hand_score_hasher: Box<HandHasher + 'a>,
     deck_hasher: Box<DeckHasher + 'a>,
     database: Box<HashDatabase + 'a>,
+    // Final dealer totals keyed like expected_with_dealer, without the player's score
+    dealer_outcome_cache: HashMap<Vec<u8>, Vec<f64>>,
+    strategy_mode: StrategyMode,
+    // Actions keyed by strategy_key, for the modes that play by total
+    total_strategy: HashMap<Vec<u8>, BJAction>,
+    hole_card_hint: HoleCardHint,
+    // What a round's net is worth, while playing for it
+    payoff: Option<Payoff>,
+    // Distributions keyed by payoff_key
+    payoff_cache: HashMap<Vec<u8>, OutcomeDistribution>,
+    // While set, the most the round can add to the first bet by doubling
+    // and splitting, in bets, when playing for a payoff
+    max_extra_stake: Option<f64>,
+    // What doubles and splits have added so far, in bets
+    extra_stake: f64,
+    // While set, distributions only count the rounds ending in this dealer
+    // outcome index, or DEALER_OUTCOMES for a dealer blackjack
+    dealer_condition: Option<usize>,
     shoe: &'b mut (DirectShoe + 'b),
     rules: BJRules,
 }
@@ -46,11 +88,61 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
             hand_score_hasher: Box::new(HandScoreHasher),
             deck_hasher: Box::new(SuitlessDeckHasher),
             database: Box::new(InMemoryHashDatabase::new()),
+            dealer_outcome_cache: HashMap::new(),
+            strategy_mode: StrategyMode::CompositionDependent,
+            total_strategy: HashMap::new(),
+            hole_card_hint: HoleCardHint::new_unknown(),
+            payoff: None,
+            payoff_cache: HashMap::new(),
+            max_extra_stake: None,
+            extra_stake: 0.0,
+            dealer_condition: None,
             rules: rules,
             shoe: shoe,
         }
     }
 
+    // The shoe being calculated against.  Put back any card taken out of it
+    pub fn shoe(&mut self) -> &mut (DirectShoe + 'b) {
+        &mut *self.shoe
+    }
+
+    /**
+      Calculates knowing the dealer's hole card is one of the hint's ranks,
+      as when the dealer flashes it.  The hole card stays in the shoe, since
+      the exact card is still unseen.  Clears the cached EVs.
+      */
+    pub fn set_hole_card_hint(&mut self, hint: HoleCardHint) {
+        self.hole_card_hint = hint;
+        self.database = Box::new(InMemoryHashDatabase::new());
+        self.dealer_outcome_cache = HashMap::new();
+        self.payoff_cache = HashMap::new();
+    }
+
+    // Whether the hole card under this up card can be v: the hint allows it
+    // and it doesn't make a blackjack the dealer would have peeked at
+    fn can_be_hole_card(&self, dealer_up_card: &Card, v: &Value) -> bool {
+        if !self.hole_card_hint.allows(v) {
+            return false;
+        }
+        if self.rules.dealer_blackjack_after_hand() {
+            return true;
+        }
+        let dealer_hand = BJHand::new_with_cards(&vec![*dealer_up_card,
+                                                       Card::new(*v, *dealer_up_card.suit())]);
+        !self.rules.is_blackjack(&dealer_hand)
+    }
+
+    fn hole_card_count(&self, dealer_up_card: &Card) -> u32 {
+        let mut ret = 0;
+        for v in VALUES.iter() {
+            if self.can_be_hole_card(dealer_up_card, v) {
+                ret += self.shoe.count(v);
+            }
+        }
+        ret
+    }
+
     fn dbget(&self, hash: &Vec<u8>) -> Option<f64> {
         TimeIt::new("dbget");
         self.database.get(hash)
@@ -63,6 +155,12 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
 
     pub fn total_expected_best_value(&mut self) -> f64 {
         TimeIt::new("total_expected_best_value");
+        if self.rules.double_exposure() {
+            return self.total_expected_double_exposure();
+        }
+        if self.rules.double_attack() {
+            return self.total_expected_double_attack();
+        }
         let mut total_ev = 0.0f64;
         let start_len = self.shoe.len();
         for dealer_up_value in VALUES.iter() {
@@ -81,6 +179,129 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
         return total_ev;
     }
 
+    /**
+      Double Exposure shows the hole card, so every up card and hole card
+      pair is played as its own game with the hole card known.  Leaves the
+      hole card hint as it was.
+      */
+    fn total_expected_double_exposure(&mut self) -> f64 {
+        let saved_hint = self.hole_card_hint;
+        let mut total_ev = 0.0f64;
+        for dealer_up_value in VALUES.iter() {
+            if self.shoe.count(dealer_up_value) == 0 {
+                continue;
+            }
+            let odds_of_dealer_up_value = (self.shoe.count(dealer_up_value) as f64) / (self.shoe.len() as f64);
+            let dealer_up_card = self.shoe.remove(dealer_up_value).unwrap();
+            for hole_value in SCORE_VALUES.iter() {
+                let hole_count = VALUES.iter().filter(
+                    |v| score_for_value(*v) == score_for_value(hole_value)).fold(
+                    0, |sum, v| sum + self.shoe.count(v));
+                if hole_count == 0 {
+                    continue;
+                }
+                let odds_of_hole_value = hole_count as f64 / self.shoe.len() as f64;
+                let dealer_hand = BJHand::new_with_cards(
+                    &vec![dealer_up_card, Card::new(*hole_value, *dealer_up_card.suit())]);
+                let ev = if self.rules.is_blackjack(&dealer_hand) {
+                    // Only a player blackjack gets anything back
+                    let hole_face = *VALUES.iter().find(|v| {
+                        score_for_value(*v) == score_for_value(hole_value) && self.shoe.count(*v) > 0
+                    }).unwrap();
+                    let hole_card = self.shoe.remove(&hole_face).unwrap();
+                    let odds_of_player_bj = self.odds_of_blackjack();
+                    self.shoe.insert(&hole_card);
+                    odds_of_player_bj * self.rules.blackjack_tie_result() - (1.0 - odds_of_player_bj)
+                } else {
+                    self.set_hole_card_hint(HoleCardHint::new_exact(hole_value));
+                    let mut hand = BJHand::new();
+                    self.expected_value_best_action(&mut hand, &dealer_up_card, true)
+                };
+                total_ev += odds_of_dealer_up_value * odds_of_hole_value * ev;
+            }
+            self.shoe.insert(&dealer_up_card);
+        }
+        self.set_hole_card_hint(saved_hint);
+        total_ev
+    }
+
+    /**
+      Double Attack lets the player double the bet after seeing the up card,
+      before the player's own cards are dealt.  The dealer peeks first, so a
+      dealer blackjack only takes the original bet.  Returns every up card
+      rank with its odds, the EV of playing for the original bet and the EV
+      of raising, both in original bets.
+      */
+    pub fn double_attack_values(&mut self) -> Vec<(Value, f64, f64, f64)> {
+        let mut ret = Vec::with_capacity(SCORE_VALUES.len());
+        for dealer_up_value in SCORE_VALUES.iter() {
+            let up_face = VALUES.iter().find(|v| {
+                score_for_value(*v) == score_for_value(dealer_up_value) && self.shoe.count(*v) > 0
+            }).map(|v| *v);
+            let up_face = match up_face {
+                Some(v) => v,
+                None => continue,
+            };
+            let up_count = VALUES.iter().filter(
+                |v| score_for_value(*v) == score_for_value(dealer_up_value)).fold(
+                0, |sum, v| sum + self.shoe.count(v));
+            let odds_of_dealer_up_value = up_count as f64 / self.shoe.len() as f64;
+            let dealer_up_card = self.shoe.remove(&up_face).unwrap();
+            let mut hand = BJHand::new();
+            let (ev, raised_ev) = if self.rules.dealer_blackjack_after_hand() {
+                let ev = self.expected_value_best_action(&mut hand, &dealer_up_card, false);
+                (ev, 2.0 * ev)
+            } else {
+                let odds_of_dealer_bj = self.odds_of_dealer_blackjack(&dealer_up_card);
+                let bj_ev = if odds_of_dealer_bj > 0.0 {
+                    odds_of_dealer_bj * self.against_dealer_blackjack(&dealer_up_card)
+                } else {
+                    0.0
+                };
+                let checked_ev = if odds_of_dealer_bj < 1.0 {
+                    (1.0 - odds_of_dealer_bj) *
+                        self.expected_value_best_action(&mut hand, &dealer_up_card, true)
+                } else {
+                    0.0
+                };
+                (bj_ev + checked_ev, bj_ev + 2.0 * checked_ev)
+            };
+            ret.push((*dealer_up_value, odds_of_dealer_up_value, ev, raised_ev));
+            self.shoe.insert(&dealer_up_card);
+        }
+        ret
+    }
+
+    // Raising whenever it's worth more than the original bet
+    fn total_expected_double_attack(&mut self) -> f64 {
+        self.double_attack_values().iter().fold(0.0, |sum, &(_, odds, ev, raised_ev)| {
+            sum + odds * if raised_ev > ev { raised_ev } else { ev }
+        })
+    }
+
+    // What the player's cards are worth once the dealer turns up a blackjack
+    // under this up card
+    fn against_dealer_blackjack(&mut self, dealer_up_card: &Card) -> f64 {
+        let mut total = 0.0;
+        let mut hole_cards = 0;
+        for v in VALUES.iter() {
+            let card_count = self.shoe.count(v);
+            if card_count == 0 || !self.hole_card_hint.allows(v) {
+                continue;
+            }
+            let down_dealer_card = self.shoe.remove(v).unwrap();
+            let dealer_hand = BJHand::new_with_cards(&vec![*dealer_up_card, down_dealer_card]);
+            if self.rules.is_blackjack(&dealer_hand) {
+                let odds_of_player_bj = self.odds_of_blackjack();
+                total += card_count as f64 * (odds_of_player_bj * self.rules.blackjack_tie_result() -
+                                              (1.0 - odds_of_player_bj));
+                hole_cards += card_count;
+            }
+            self.shoe.insert(&down_dealer_card);
+        }
+        if hole_cards == 0 { -1.0 } else { total / hole_cards as f64 }
+    }
+
     pub fn odds_of_blackjack(&self) -> f64 {
         let ace_count = self.shoe.count(&ACE);
         if ace_count == 0 {
@@ -92,9 +313,13 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
         }
 
         // Odds of (Ace + 10) + (10 + Ace)
-
-        return (ace_count as f64 / self.shoe.len() as f64) * (ten_count as f64 / (self.shoe.len() as f64 - 1.0)) +
-            (ten_count as f64/ self.shoe.len() as f64) * (ace_count as f64 / (self.shoe.len() as f64 - 1.0));
+        // The first card doesn't change an infinite shoe
+        let after_first = match self.shoe.initial_length() {
+            None => self.shoe.len() as f64,
+            Some(_) => self.shoe.len() as f64 - 1.0,
+        };
+        return (ace_count as f64 / self.shoe.len() as f64) * (ten_count as f64 / after_first) +
+            (ten_count as f64/ self.shoe.len() as f64) * (ace_count as f64 / after_first);
     }
 
     pub fn initial_hand(&self, hand: &BJHand) -> bool {
@@ -104,6 +329,199 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
             hand.double_count() == 0;
     }
 
+    // Odds the dealer's down card completes a blackjack with this up card
+    pub fn odds_of_dealer_blackjack(&mut self, dealer_up_card: &Card) -> f64 {
+        let mut odds_of_dealer_bj = 0.0;
+        let mut possible_cards = 0;
+        for v in VALUES.iter() {
+            if self.hole_card_hint.allows(v) {
+                possible_cards += self.shoe.count(v);
+            }
+        }
+        for v in VALUES.iter() {
+            let card_count = self.shoe.count(v);
+            if card_count == 0 || !self.hole_card_hint.allows(v) {
+                continue;
+            }
+            let odds_of_this_value = card_count as f64 / possible_cards as f64;
+            let down_dealer_card  = self.shoe.remove(v).unwrap();
+            let dealer_hand = BJHand::new_with_cards(&vec![*dealer_up_card, down_dealer_card]);
+            if self.rules.is_blackjack(&dealer_hand) {
+                odds_of_dealer_bj += odds_of_this_value;
+            }
+            self.shoe.insert(&down_dealer_card);
+        }
+        odds_of_dealer_bj
+    }
+
+    // The legal action with the highest EV, and that EV
+    pub fn best_action(&mut self, hand: &mut BJHand, dealer_up_card: &Card,
+                       has_dealer_checked_bj: bool) -> Option<(BJAction, f64)> {
+        let mut best_result: Option<(BJAction, f64)> = None;
+        for a in BJ_ACTIONS.iter() {
+            match self.expected_value(hand, dealer_up_card, *a, has_dealer_checked_bj) {
+                Some(r) => {
+                    match best_result {
+                        Some((_, b)) => {
+                            if b < r {
+                                best_result = Some((*a, r));
+                            }
+                        }
+                        None => {
+                            best_result = Some((*a, r));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        best_result
+    }
+
+    /**
+      Plays every hand the mode doesn't decide from the exact cards by the
+      table from total_dependent_strategy(), so expected_value_best_action()
+      and the outcome distributions give the EV of that strategy.  Hands the
+      table has no entry for get the best action.  Clears the cached EVs.
+      */
+    pub fn set_strategy(&mut self, mode: StrategyMode, table: HashMap<Vec<u8>, BJAction>) {
+        self.strategy_mode = mode;
+        self.total_strategy = table;
+        self.database = Box::new(InMemoryHashDatabase::new());
+    }
+
+    // The hand's total, softness, legal actions and splits, and the up card
+    fn strategy_key(&self, hand: &BJHand, dealer_up_card: &Card) -> Vec<u8> {
+        let mut ret = self.player_hand_hasher.hash_hand(&self.rules, hand);
+        ret.push(score_for_value(dealer_up_card.value()) as u8);
+        ret
+    }
+
+    // The action the strategy mode plays, and its EV
+    fn strategy_action(&mut self, hand: &mut BJHand, dealer_up_card: &Card,
+                       has_dealer_checked_bj: bool) -> Option<(BJAction, f64)> {
+        let fixed = match self.strategy_mode {
+            StrategyMode::CompositionDependent => None,
+            StrategyMode::TwoCardCompositionDependent if hand.len() <= 2 => None,
+            _ => self.total_strategy.get(&self.strategy_key(hand, dealer_up_card)).map(|a| *a),
+        };
+        match fixed {
+            Some(a) => self.expected_value(hand, dealer_up_card, a, has_dealer_checked_bj).map(
+                |ev| (a, ev)),
+            None => self.best_action(hand, dealer_up_card, has_dealer_checked_bj),
+        }
+    }
+
+    /**
+      A total dependent strategy for the shoe: for every total and up card,
+      the action with the best EV summed over every way of reaching it,
+      weighted by how likely each is.  Hands are reached playing the current
+      strategy and the EVs assume it from there on, so this is one step of
+      improvement on it rather than the very best total dependent strategy.
+      Split hands after the first are played like the first.
+      */
+    pub fn total_dependent_strategy(&mut self) -> HashMap<Vec<u8>, BJAction> {
+        let mut totals = HashMap::new();
+        for dealer_up_value in VALUES.iter() {
+            if self.shoe.count(dealer_up_value) == 0 {
+                continue;
+            }
+            let odds_of_dealer_up_value = (self.shoe.count(dealer_up_value) as f64) / (self.shoe.len() as f64);
+            let dealer_up_card = self.shoe.remove(dealer_up_value).unwrap();
+            let mut hand = BJHand::new();
+            self.gather_decisions(&mut hand, &dealer_up_card, false, odds_of_dealer_up_value,
+                                  &mut totals);
+            self.shoe.insert(&dealer_up_card);
+        }
+        totals.into_iter().map(|(key, sums)| {
+            let mut best: Option<(BJAction, f64)> = None;
+            for (a, sum) in BJ_ACTIONS.iter().zip(sums.iter()) {
+                match (*sum, best) {
+                    (Some(s), Some((_, b))) if s <= b => {}
+                    (Some(s), _) => best = Some((*a, s)),
+                    (None, _) => {}
+                }
+            }
+            (key, best.unwrap().0)
+        }).collect()
+    }
+
+    // Adds the EV of every legal action at every decision, weighted by the
+    // odds of getting there, to the decision's totals
+    fn gather_decisions(&mut self, hand: &mut BJHand, dealer_up_card: &Card,
+                        has_dealer_checked_bj: bool, reach: f64,
+                        totals: &mut HashMap<Vec<u8>, Vec<Option<f64>>>) {
+        if self.initial_hand(hand) && !self.rules.dealer_blackjack_after_hand() && !has_dealer_checked_bj {
+            if self.rules.is_blackjack(hand) {
+                return;
+            }
+            let odds_of_dealer_bj = self.odds_of_dealer_blackjack(dealer_up_card);
+            return self.gather_decisions(hand, dealer_up_card, true,
+                                         reach * (1.0 - odds_of_dealer_bj), totals);
+        }
+        let mut evs = Vec::with_capacity(BJ_ACTIONS.len());
+        for (i, a) in BJ_ACTIONS.iter().enumerate() {
+            match self.expected_value(hand, dealer_up_card, *a, has_dealer_checked_bj) {
+                Some(ev) => evs.push((i, ev)),
+                None => {}
+            }
+        }
+        if evs.len() > 1 {
+            let key = self.strategy_key(hand, dealer_up_card);
+            if !totals.contains_key(&key) {
+                totals.insert(key.clone(), vec![None; BJ_ACTIONS.len()]);
+            }
+            let sums = totals.get_mut(&key).unwrap();
+            for &(i, ev) in evs.iter() {
+                sums[i] = Some(sums[i].unwrap_or(0.0) + reach * ev);
+            }
+        }
+        let action = match self.strategy_action(hand, dealer_up_card, has_dealer_checked_bj) {
+            Some((a, _)) => a,
+            None => return,
+        };
+        match action {
+            HIT => {
+                for v in VALUES.iter() {
+                    let odds_of_value = self.odds_of_value(dealer_up_card, v);
+                    if odds_of_value != 0.0 {
+                        let card_from_deck = self.shoe.remove(v).unwrap();
+                        hand.add_card(&card_from_deck);
+                        self.gather_decisions(hand, dealer_up_card, has_dealer_checked_bj,
+                                              reach * odds_of_value, totals);
+                        hand.remove_card(&card_from_deck);
+                        self.shoe.insert(&card_from_deck);
+                    }
+                }
+            }
+            SPLIT => {
+                hand.split();
+                self.gather_decisions(hand, dealer_up_card, has_dealer_checked_bj, reach, totals);
+                hand.unsplit();
+            }
+            _ => {}
+        }
+    }
+
+    /**
+      The EV of the whole game under each strategy mode, so the gain from
+      each level of sophistication can be quoted.  The modes that play by
+      total use the table from total_dependent_strategy(), so their EVs are
+      a lower bound on the best strategy of that kind.  Leaves the
+      calculator playing the composition dependent strategy.
+      */
+    pub fn strategy_mode_values(&mut self) -> Vec<(StrategyMode, f64)> {
+        self.set_strategy(StrategyMode::CompositionDependent, HashMap::new());
+        let table = self.total_dependent_strategy();
+        let mut ret = Vec::with_capacity(STRATEGY_MODES.len());
+        for mode in STRATEGY_MODES.iter() {
+            self.set_strategy(*mode, table.clone());
+            ret.push((*mode, self.total_expected_best_value()));
+        }
+        self.set_strategy(StrategyMode::CompositionDependent, HashMap::new());
+        ret
+    }
+
     pub fn expected_value_best_action(&mut self, hand: &mut BJHand,
                                       dealer_up_card: &Card, has_dealer_checked_bj: bool) -> f64 {
         TimeIt::new("expected_value_best_action");
@@ -112,6 +530,11 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
             v2.push_all(self.deck_hasher.hash_deck(&self.rules, &*self.shoe).as_slice());
             v2.push(dealer_up_card.value().index() as u8);
             v2.push(has_dealer_checked_bj as u8);
+            // Two card hands are played by their cards and the rest by the
+            // table, even when the shoe hashes the same
+            if self.strategy_mode == StrategyMode::TwoCardCompositionDependent {
+                v2.push(hand.len() as u8);
+            }
             v2
         };
         match self.dbget(&v1) {
@@ -119,24 +542,16 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
             None => {}
         }
         if self.initial_hand(hand) && !self.rules.dealer_blackjack_after_hand() && !has_dealer_checked_bj {
-            let mut odds_of_dealer_bj = 0.0;
-            for v in VALUES.iter() {
-                let card_count = self.shoe.count(v);
-                if card_count == 0 {
-                    continue;
-                }
-                let odds_of_this_value = card_count as f64 / self.shoe.len() as f64;
-                let down_dealer_card  = self.shoe.remove(v).unwrap();
-                let dealer_hand = BJHand::new_with_cards(&vec![*dealer_up_card, down_dealer_card]);
-                if self.rules.is_blackjack(&dealer_hand) {
-                    odds_of_dealer_bj += odds_of_this_value;
-                }
-                self.shoe.insert(&down_dealer_card);
+            let odds_of_dealer_bj = self.odds_of_dealer_blackjack(dealer_up_card);
+            if odds_of_dealer_bj >= 1.0 {
+                // The hole card is known to make a blackjack
+                return if self.rules.is_blackjack(hand) { self.rules.blackjack_tie_result() } else { -1.0 };
             }
             if self.rules.is_blackjack(hand) {
                 return (1.0 - odds_of_dealer_bj) * self.expected_value_best_action(hand,
                                                                                    dealer_up_card,
-                                                                                   true);
+                                                                                   true) +
+                    odds_of_dealer_bj * self.rules.blackjack_tie_result();
             } else {
                 return (1.0 - odds_of_dealer_bj) * self.expected_value_best_action(hand,
                                                                                    dealer_up_card,
@@ -144,28 +559,10 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
                     odds_of_dealer_bj * -1.0;
             }
         }
-        let mut best_result: Option<f64>  = None;
-        // TODO: Can I just do something like BJAction.variants ??
-        let actions = [STAND, HIT, DOUBLE, SPLIT, SURRENDER];
-        for a in actions.iter() {
-            match self.expected_value(hand, dealer_up_card, *a, has_dealer_checked_bj) {
-                Some(r) => {
-                    match best_result {
-                        Some(b) => {
-                            if b < r {
-                                best_result = Some(r);
-                            }
-                        }
-                        None => {
-                            best_result = Some(r);
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
-        assert!(best_result != None);
-        let to_return = best_result.unwrap();
+        let to_return = match self.strategy_action(hand, dealer_up_card, has_dealer_checked_bj) {
+            Some((_, ev)) => ev,
+            None => panic!("No legal action for this hand"),
+        };
         match self.dbstore(&v1, to_return) {
             Some(_) => {
                 panic!("Logic loop????...")
@@ -176,53 +573,71 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
         }
     }
 
+    /**
+      The EV of every legal action for the player's starting cards against a
+      dealer up card, once the dealer has checked for blackjack.  The cards
+      are taken out of the shoe for the calculation and put back afterwards.
+      Returns None if the shoe doesn't hold the requested cards.
+      */
+    pub fn action_values(&mut self, player_values: &Vec<Value>,
+                         dealer_up_value: &Value) -> Option<Vec<(BJAction, f64)>> {
+        let mut removed: Vec<Card> = Vec::with_capacity(player_values.len() + 1);
+        for v in player_values.iter().chain(Some(dealer_up_value).into_iter()) {
+            match self.shoe.remove(v) {
+                Some(c) => removed.push(c),
+                None => {
+                    for c in removed.iter() {
+                        self.shoe.insert(c);
+                    }
+                    return None;
+                }
+            }
+        }
+        let dealer_up_card = removed.pop().unwrap();
+        let mut hand = BJHand::new_with_cards(&removed);
+        let mut ret = Vec::with_capacity(BJ_ACTIONS.len());
+        for a in BJ_ACTIONS.iter() {
+            match self.expected_value(&mut hand, &dealer_up_card, *a, true) {
+                Some(ev) => ret.push((*a, ev)),
+                None => {}
+            }
+        }
+        self.shoe.insert(&dealer_up_card);
+        for c in removed.iter() {
+            self.shoe.insert(c);
+        }
+        Some(ret)
+    }
+
     fn odds_of_value(&mut self, dealer_up_card: &Card, v: &Value) -> f64 {
         TimeIt::new("odds_of_value");
         let count_of_val = self.shoe.count(v) as u64;
         if count_of_val == 0 {
             return 0.0;
         }
-        // Tricky: If the dealer is showing a TEN, then
-        //         you're more likely to get a ACE than
-        //         any other card because you know the
-        //         dealer doesn't have one of your aces
-        let (num_valid_down_cards, invalid_down_val) = {
-            match self.shoe.initial_length() {
-                Some(_) => {
-                    if self.rules.dealer_blackjack_after_hand() {
-                        (self.shoe.len() as u32, None)
-                    } else {
-                        match score_for_value(dealer_up_card.value()) {
-                            10 => (self.shoe.len() as u32 - self.shoe.count(&ACE), Some(score_for_value(&ACE))),
-                            11 => (self.shoe.len() as u32 - self.shoe.count(&TEN) - self.shoe.count(&JACK) - self.shoe.count(&QUEEN) -
-                               self.shoe.count(&KING), Some(score_for_value(&TEN))),
-                            _ => (self.shoe.len() as u32, None)
-                        }
-                    }
-                },
-                // The second None here is KIND OF a lie ... oh well
-                None => (self.shoe.len() as u32, None)
-            }
-        };
+        // Tricky: The dealer's hole card came out of the shoe first.  If
+        //         the dealer is showing a TEN, then you're more likely to
+        //         get a ACE than any other card because you know the dealer
+        //         doesn't have one of your aces.  Same for any hint about it.
         // Deck is [1,2, 2, 3, 3, 4, 4, 4]
-        //  Odds of a 3 (when you know the dealer doesn't have 4) are: 
+        //  Odds of a 3 (when you know the dealer doesn't have 4) are:
         //   (2/5) * (1/7) + (3/5) * (2/7)
         // Odds of a 3 are odds of sum of:
         //  (1) Dealer has three * odds of another three
         //  (2) Dealer does not have three * odds of your three
+        // which comes to count * (1 - 1 / possible hole cards) / (len - 1)
         match self.shoe.initial_length() {
+            // The hole card doesn't change an infinite shoe
             None => count_of_val as f64 / self.shoe.len() as f64,
-            Some(_) =>
-                match invalid_down_val == Some(score_for_value(v)) {
-                true =>
-                  count_of_val as f64 / (self.shoe.len() - 1) as f64,
-                false =>
-                   (count_of_val as f64 / num_valid_down_cards as f64) *
-                    ((count_of_val - 1) as f64 / (self.shoe.len() - 1) as f64)
-                    +
-                    ((num_valid_down_cards as u64 - count_of_val) as f64 /
-                     num_valid_down_cards as f64) *
-                    ((count_of_val) as f64 / (self.shoe.len() - 1) as f64),
+            Some(_) => {
+                let hole_cards = self.hole_card_count(dealer_up_card);
+                let could_be_hole = hole_cards > 0 && self.can_be_hole_card(dealer_up_card, v);
+                let left_for_player = if could_be_hole {
+                    count_of_val as f64 * (1.0 - 1.0 / hole_cards as f64)
+                } else {
+                    count_of_val as f64
+                };
+                left_for_player / (self.shoe.len() - 1) as f64
             }
         }
     }
@@ -268,6 +683,8 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
                     false => {
                         if hand.score() > 21 {
                             -1.0
+                        } else if self.rules.is_automatic_win(hand) {
+                            self.rules.five_card_trick_payout()
                         } else {
                             let mut dealer_hand = BJHand::new();
                             dealer_hand.add_card(dealer_up_card);
@@ -290,6 +707,7 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
                 // Note: We support DaS, but something like SaD wouldn't work
                 //       with this flow.
                 let mut current_hand = hand.without_split_information();
+                let bet_factor = self.rules.double_bet_factor(hand);
                 for v in VALUES.iter() {
                     let odds_of_value = self.odds_of_value(dealer_up_card, v);
                     if odds_of_value != 0.0 {
@@ -305,7 +723,7 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
                         // any left over splits...  it is *NOT* twice resolving this
                         // hand plus a card since we carry on the card inside the hand
                         let ev_with_value =
-                            2.0 * self.expected_value_best_action(
+                            bet_factor * self.expected_value_best_action(
                                 &mut current_hand, dealer_up_card, has_dealer_checked_bj);
                         final_result += odds_of_value * ev_with_value;
                         current_hand.remove_card(&card_from_deck);
@@ -375,6 +793,8 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
                 return -1.0;
             } else if dealer_score < player_score {
                 return 1.0;
+            } else if self.rules.dealer_wins_ties() {
+                return -1.0;
             } else {
                 return 0.0;
             }
@@ -391,24 +811,19 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
             }
             // The dealer hits ... takes a random card
             let mut final_result = 0.0;
-            // Limit the number of valid cards on the first hand if
-            // you already nkow the dealer doesn't have blackjack
-            let number_of_valid_cards = {
-                if self.rules.dealer_blackjack_after_hand() ||
-                    dealer_hand.len() != 1 {
-                        self.shoe.len() as u64
-                } else {
-                    match dealer_hand.score() {
-                        10 => self.shoe.len() as u64 - self.shoe.count(&ACE) as u64,
-                        11 => self.shoe.len() as u64 - (self.shoe.count(&TEN) + self.shoe.count(&JACK) + self.shoe.count(&QUEEN) +
-                           self.shoe.count(&KING)) as u64,
-                        _ => self.shoe.len() as u64
-                    }
-                }
+            // The first card is the hole card, which can only be what the
+            // player knows it could be.  If the dealer checked for
+            // blackjack, it isn't one
+            let hole_card = dealer_hand.len() == 1;
+            let number_of_valid_cards = if hole_card {
+                self.hole_card_count(&dealer_hand.cards()[0]) as u64
+            } else {
+                self.shoe.len() as u64
             };
             for &v in VALUES.iter() {
                 let count_of_val = self.shoe.count(&v);
-                if count_of_val > 0 {
+                if count_of_val > 0 &&
+                    (!hole_card || self.can_be_hole_card(&dealer_hand.cards()[0], &v)) {
                     let odds_of_value =
                         count_of_val as f64 /
                         number_of_valid_cards as f64;
@@ -420,14 +835,9 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
                     };
                     assert_eq!(card_from_deck.value().desc(), v.desc());
                     dealer_hand.add_card(&card_from_deck);
-                    if self.rules.is_blackjack(dealer_hand) &&
-                        !self.rules.dealer_blackjack_after_hand() {
-                            // ignore
-                    } else {
-                        let ev_with_value = self.expected_with_dealer(
-                            player_hand, dealer_hand);
-                        final_result += odds_of_value * ev_with_value;
-                    }
+                    let ev_with_value = self.expected_with_dealer(
+                        player_hand, dealer_hand);
+                    final_result += odds_of_value * ev_with_value;
 
                     dealer_hand.remove_card(&card_from_deck);
                     self.shoe.insert(&card_from_deck);
@@ -443,6 +853,499 @@ impl <'a, 'b>ActionCalculator<'a, 'b> {
             }
         }
     }
+
+    /**
+      Odds of each final dealer total, indexed 17, 18, 19, 20, 21 then bust.
+      Like expected_with_dealer, a single card hand is assumed to have been
+      checked for blackjack.
+      */
+    pub fn dealer_outcomes(&mut self, dealer_hand: &mut BJHand) -> Vec<f64> {
+        TimeIt::new("dealer_outcomes");
+        let mut ret = vec![0.0; DEALER_OUTCOMES];
+        if !self.rules.should_hit_dealer_hand(dealer_hand) {
+            ret[dealer_outcome_index(dealer_hand.score())] = 1.0;
+            return ret;
+        }
+        let v1 = {
+            let mut v2 = self.dealer_hand_hasher.hash_hand(&self.rules, dealer_hand);
+            v2.push_all(self.deck_hasher.hash_deck(&self.rules, &*self.shoe).as_slice());
+            v2
+        };
+        match self.dealer_outcome_cache.get(&v1) {
+            Some(s) => return s.clone(),
+            None => {}
+        }
+        let hole_card = dealer_hand.len() == 1;
+        let number_of_valid_cards = if hole_card {
+            self.hole_card_count(&dealer_hand.cards()[0]) as u64
+        } else {
+            self.shoe.len() as u64
+        };
+        for &v in VALUES.iter() {
+            let count_of_val = self.shoe.count(&v);
+            if count_of_val == 0 || (hole_card && !self.can_be_hole_card(&dealer_hand.cards()[0], &v)) {
+                continue;
+            }
+            let odds_of_value = count_of_val as f64 / number_of_valid_cards as f64;
+            let card_from_deck = self.shoe.remove(&v).unwrap();
+            dealer_hand.add_card(&card_from_deck);
+            let sub = self.dealer_outcomes(dealer_hand);
+            for i in range(0, DEALER_OUTCOMES) {
+                ret[i] += odds_of_value * sub[i];
+            }
+            dealer_hand.remove_card(&card_from_deck);
+            self.shoe.insert(&card_from_deck);
+        }
+        self.dealer_outcome_cache.insert(v1, ret.clone());
+        ret
+    }
+
+    /**
+      The distribution of the round's net result when the player follows the
+      best action at every step.  Unlike the EV recursion, every split hand is
+      played out from the shoe the earlier hands left behind and all of them
+      are settled against the same dealer hand, so the variance includes how
+      split hands win and lose together.
+      */
+    pub fn outcome_distribution(&mut self, hand: &mut BJHand, dealer_up_card: &Card,
+                                has_dealer_checked_bj: bool) -> OutcomeDistribution {
+        let mut settled = Vec::new();
+        self.distribution_best_action(hand, None, dealer_up_card, has_dealer_checked_bj,
+                                      1.0, &mut settled, 0.0)
+    }
+
+    pub fn total_outcome_distribution(&mut self) -> OutcomeDistribution {
+        let mut ret = OutcomeDistribution::new();
+        for dealer_up_value in VALUES.iter() {
+            if self.shoe.count(dealer_up_value) == 0 {
+                continue;
+            }
+            let odds_of_dealer_up_value = (self.shoe.count(dealer_up_value) as f64) / (self.shoe.len() as f64);
+            let dealer_up_card = self.shoe.remove(dealer_up_value).unwrap();
+            let mut hand = BJHand::new();
+            let d = self.outcome_distribution(&mut hand, &dealer_up_card, false);
+            ret.mix(&d, odds_of_dealer_up_value);
+            self.shoe.insert(&dealer_up_card);
+        }
+        ret
+    }
+
+    /**
+      The EV and variance of a hand, and the covariance of two hands, when
+      several are played at once against one dealer hand, as if from an
+      infinite deck.  Each hand is played the way total_outcome_distribution
+      plays a round.  They only move together through the dealer's hand:
+      each is worked out as if it had the shoe to itself, so the cards one
+      takes don't change the others.  That's exact for an infinite shoe and
+      an approximation for any other.
+      */
+    pub fn multiple_hands_infinite_deck(&mut self, hands: u32) -> MultipleHands {
+        let mut all = OutcomeDistribution::new();
+        // The sum over up cards and dealer outcomes of the odds of each
+        // times the square of a hand's mean given it, E[E[X|dealer]^2]
+        let mut squared_means = 0.0;
+        for dealer_up_value in VALUES.iter() {
+            if self.shoe.count(dealer_up_value) == 0 {
+                continue;
+            }
+            let odds_of_dealer_up_value = (self.shoe.count(dealer_up_value) as f64) / (self.shoe.len() as f64);
+            let dealer_up_card = self.shoe.remove(dealer_up_value).unwrap();
+            for dealer_index in range(0, DEALER_OUTCOMES + 1) {
+                self.dealer_condition = Some(dealer_index);
+                let mut hand = BJHand::new();
+                // Probabilities and mean are both of the hand and this outcome together
+                let d = self.outcome_distribution(&mut hand, &dealer_up_card, false);
+                let odds = d.total_probability();
+                if odds > 0.0 {
+                    squared_means += odds_of_dealer_up_value * d.mean() * d.mean() / odds;
+                }
+                all.mix(&d, odds_of_dealer_up_value);
+            }
+            self.dealer_condition = None;
+            self.shoe.insert(&dealer_up_card);
+        }
+        let ev = all.mean();
+        MultipleHands::new(hands, ev, all.variance(), squared_means - ev * ev)
+    }
+
+    /**
+      The distribution of the round's net result when the player plays every
+      hand for the most payoff instead of the most units.
+      */
+    pub fn payoff_distribution(&mut self, payoff: Payoff) -> OutcomeDistribution {
+        self.payoff = Some(payoff);
+        let ret = self.total_outcome_distribution();
+        self.payoff = None;
+        ret
+    }
+
+    /**
+      The expected payoff of every legal action for the player's starting
+      cards against a dealer up card, once the dealer has checked for
+      blackjack, like action_values does for EVs.
+      */
+    pub fn payoff_action_values(&mut self, player_values: &Vec<Value>, dealer_up_value: &Value,
+                                payoff: Payoff) -> Option<Vec<(BJAction, f64)>> {
+        let mut removed: Vec<Card> = Vec::with_capacity(player_values.len() + 1);
+        for v in player_values.iter().chain(Some(dealer_up_value).into_iter()) {
+            match self.shoe.remove(v) {
+                Some(c) => removed.push(c),
+                None => {
+                    for c in removed.iter() {
+                        self.shoe.insert(c);
+                    }
+                    return None;
+                }
+            }
+        }
+        let dealer_up_card = removed.pop().unwrap();
+        let mut hand = BJHand::new_with_cards(&removed);
+        self.payoff = Some(payoff);
+        let mut ret = Vec::with_capacity(BJ_ACTIONS.len());
+        for a in BJ_ACTIONS.iter() {
+            if self.rules.can_take_action(&hand, *a) && self.can_afford(&hand, *a, 1.0) {
+                let mut settled = Vec::new();
+                let d = self.distribution_of_action(*a, &mut hand, None, &dealer_up_card, true,
+                                                    1.0, &mut settled, 0.0);
+                ret.push((*a, payoff.expected(&d)));
+            }
+        }
+        self.payoff = None;
+        self.shoe.insert(&dealer_up_card);
+        for c in removed.iter() {
+            self.shoe.insert(c);
+        }
+        Some(ret)
+    }
+
+    /**
+      Tournament play, where all that counts is ending the round with at
+      least target chips.  Returns the odds of reaching the target betting
+      bet out of chips.  Doubles and splits can only stake the chips left
+      after the bet.
+      */
+    pub fn tournament_odds(&mut self, chips: f64, bet: f64, target: f64) -> f64 {
+        if bet <= 0.0 {
+            return if chips >= target { 1.0 } else { 0.0 };
+        }
+        let need = (target - chips) / bet;
+        self.max_extra_stake = Some((chips - bet) / bet);
+        let ret = self.payoff_distribution(Payoff::AtLeast(need)).probability_at_least(need);
+        self.max_extra_stake = None;
+        ret
+    }
+
+    // The bet out of bets most likely to reach the target, and its odds
+    pub fn tournament_best_bet(&mut self, chips: f64, target: f64,
+                               bets: &[f64]) -> Option<(f64, f64)> {
+        let mut best: Option<(f64, f64)> = None;
+        for &bet in bets.iter().filter(|b| **b <= chips) {
+            let odds = self.tournament_odds(chips, bet, target);
+            match best {
+                Some((_, best_odds)) if best_odds >= odds => {}
+                _ => best = Some((bet, odds)),
+            }
+        }
+        best
+    }
+
+    // The odds of reaching the target for every legal action the chips left
+    // after the bet pay for
+    pub fn tournament_action_values(&mut self, player_values: &Vec<Value>,
+                                    dealer_up_value: &Value, chips: f64, bet: f64,
+                                    target: f64) -> Option<Vec<(BJAction, f64)>> {
+        if bet <= 0.0 {
+            // Nothing is at stake, whatever the play
+            let odds = if chips >= target { 1.0 } else { 0.0 };
+            return self.payoff_action_values(player_values, dealer_up_value, Payoff::Units).map(
+                |values| values.into_iter().map(|(a, _)| (a, odds)).collect());
+        }
+        self.max_extra_stake = Some((chips - bet) / bet);
+        let ret = self.payoff_action_values(player_values, dealer_up_value,
+                                            Payoff::AtLeast((target - chips) / bet));
+        self.max_extra_stake = None;
+        ret
+    }
+
+    // Whether the action's extra stake fits under max_extra_stake
+    fn can_afford(&self, hand: &BJHand, action: BJAction, bet: f64) -> bool {
+        let extra = match action {
+            DOUBLE => (self.rules.double_bet_factor(hand) - 1.0) * bet,
+            // Every split hand starts with the first bet
+            SPLIT => 1.0,
+            _ => 0.0,
+        };
+        match self.max_extra_stake {
+            Some(max) => extra == 0.0 || self.extra_stake + extra <= max + 0.0000001,
+            None => true,
+        }
+    }
+
+    // settled holds (score, bet) of split hands standing and waiting on the
+    // dealer, fixed the units already won or lost by the others.  next is the
+    // hand whose splits remain once this one is done, if it isn't this hand.
+    fn distribution_best_action(&mut self, hand: &mut BJHand, next: Option<&BJHand>,
+                                dealer_up_card: &Card, has_dealer_checked_bj: bool, bet: f64,
+                                settled: &mut Vec<(u32, f64)>, fixed: f64) -> OutcomeDistribution {
+        if self.initial_hand(hand) && !self.rules.dealer_blackjack_after_hand() && !has_dealer_checked_bj {
+            let odds_of_dealer_bj = self.odds_of_dealer_blackjack(dealer_up_card);
+            let mut ret = OutcomeDistribution::new();
+            let dealer_bj_result = if self.rules.is_blackjack(hand) {
+                self.rules.blackjack_tie_result() * bet
+            } else {
+                -bet
+            };
+            let condition = self.dealer_condition;
+            if condition.map_or(true, |d| d == DEALER_OUTCOMES) {
+                ret.add(fixed + dealer_bj_result, odds_of_dealer_bj);
+            }
+            if odds_of_dealer_bj < 1.0 && condition != Some(DEALER_OUTCOMES) {
+                let rest = self.distribution_best_action(hand, next, dealer_up_card, true,
+                                                         bet, settled, fixed);
+                ret.mix(&rest, 1.0 - odds_of_dealer_bj);
+            }
+            return ret;
+        }
+        let action = match self.payoff {
+            Some(payoff) => return self.distribution_for_payoff(hand, next, dealer_up_card,
+                                                                has_dealer_checked_bj, bet, settled,
+                                                                fixed, payoff),
+            None => match self.strategy_action(hand, dealer_up_card, has_dealer_checked_bj) {
+                Some((a, _)) => a,
+                None => panic!("No legal action for this hand"),
+            },
+        };
+        self.distribution_of_action(action, hand, next, dealer_up_card, has_dealer_checked_bj,
+                                    bet, settled, fixed)
+    }
+
+    // The distribution of taking the action now, and the best one after
+    fn distribution_of_action(&mut self, action: BJAction, hand: &mut BJHand,
+                              next: Option<&BJHand>, dealer_up_card: &Card,
+                              has_dealer_checked_bj: bool, bet: f64,
+                              settled: &mut Vec<(u32, f64)>, fixed: f64) -> OutcomeDistribution {
+        match action {
+            HIT => {
+                let mut ret = OutcomeDistribution::new();
+                for v in VALUES.iter() {
+                    let odds_of_value = self.odds_of_value(dealer_up_card, v);
+                    if odds_of_value != 0.0 {
+                        let card_from_deck = self.shoe.remove(v).unwrap();
+                        hand.add_card(&card_from_deck);
+                        let d = self.distribution_best_action(hand, next, dealer_up_card,
+                                                              has_dealer_checked_bj, bet,
+                                                              settled, fixed);
+                        ret.mix(&d, odds_of_value);
+                        hand.remove_card(&card_from_deck);
+                        self.shoe.insert(&card_from_deck);
+                    }
+                }
+                ret
+            }
+            DOUBLE => {
+                let mut ret = OutcomeDistribution::new();
+                let mut current_hand = hand.without_split_information();
+                let bet_factor = self.rules.double_bet_factor(hand);
+                self.extra_stake += (bet_factor - 1.0) * bet;
+                for v in VALUES.iter() {
+                    let odds_of_value = self.odds_of_value(dealer_up_card, v);
+                    if odds_of_value != 0.0 {
+                        let card_from_deck = self.shoe.remove(v).unwrap();
+                        current_hand.add_card(&card_from_deck);
+                        current_hand.add_double_count();
+                        // The doubled copy has no splits, so the original
+                        // hand's splits carry on after it
+                        let d = self.distribution_best_action(&mut current_hand,
+                                                              Some(next.unwrap_or(&*hand)),
+                                                              dealer_up_card,
+                                                              has_dealer_checked_bj, bet_factor * bet,
+                                                              settled, fixed);
+                        ret.mix(&d, odds_of_value);
+                        current_hand.remove_card(&card_from_deck);
+                        current_hand.subtract_double_count();
+                        self.shoe.insert(&card_from_deck);
+                    }
+                }
+                self.extra_stake -= (bet_factor - 1.0) * bet;
+                ret
+            }
+            SPLIT => {
+                hand.split();
+                self.extra_stake += 1.0;
+                let ret = self.distribution_best_action(hand, next, dealer_up_card,
+                                                        has_dealer_checked_bj, bet, settled, fixed);
+                self.extra_stake -= 1.0;
+                hand.unsplit();
+                ret
+            }
+            STAND => {
+                let standing = if self.rules.is_blackjack(hand) {
+                    None
+                } else if hand.score() > 21 || self.rules.is_automatic_win(hand) {
+                    None
+                } else {
+                    Some(hand.score())
+                };
+                let fixed = match standing {
+                    Some(score) => {
+                        settled.push((score, bet));
+                        fixed
+                    }
+                    None if hand.score() > 21 => fixed - bet,
+                    None if self.rules.is_automatic_win(hand) => {
+                        fixed + self.rules.five_card_trick_payout() * bet
+                    }
+                    None => fixed + self.rules.blackjack_payout() * bet,
+                };
+                let ret = self.finish_distribution(next.unwrap_or(&*hand), dealer_up_card,
+                                                   has_dealer_checked_bj, settled, fixed);
+                if standing.is_some() {
+                    settled.pop();
+                }
+                ret
+            }
+            SURRENDER => {
+                self.finish_distribution(next.unwrap_or(&*hand), dealer_up_card,
+                                         has_dealer_checked_bj, settled, fixed - 0.5 * bet)
+            }
+        }
+    }
+
+    // The action with the most expected payoff, the one with the higher EV
+    // on a tie
+    fn distribution_for_payoff(&mut self, hand: &mut BJHand, next: Option<&BJHand>,
+                               dealer_up_card: &Card, has_dealer_checked_bj: bool, bet: f64,
+                               settled: &mut Vec<(u32, f64)>, fixed: f64,
+                               payoff: Payoff) -> OutcomeDistribution {
+        let key = self.payoff_key(hand, next, dealer_up_card, has_dealer_checked_bj, bet,
+                                  settled, fixed, payoff);
+        match self.payoff_cache.get(&key) {
+            Some(d) => return d.clone(),
+            None => {}
+        }
+        let mut best: Option<(f64, OutcomeDistribution)> = None;
+        for a in BJ_ACTIONS.iter() {
+            // A blackjack is paid right away
+            if !self.rules.can_take_action(hand, *a) || (self.rules.is_blackjack(hand) && *a != STAND) ||
+                !self.can_afford(hand, *a, bet) {
+                continue;
+            }
+            let d = self.distribution_of_action(*a, hand, next, dealer_up_card,
+                                                has_dealer_checked_bj, bet, settled, fixed);
+            let p = payoff.expected(&d);
+            let better = match best {
+                Some((best_p, ref best_d)) => p > best_p || (p == best_p && d.mean() > best_d.mean()),
+                None => true,
+            };
+            if better {
+                best = Some((p, d));
+            }
+        }
+        let ret = match best {
+            Some((_, d)) => d,
+            None => panic!("No legal action for this hand"),
+        };
+        self.payoff_cache.insert(key, ret.clone());
+        ret
+    }
+
+    // Everything distribution_for_payoff depends on, amounts in hundredths
+    fn payoff_key(&self, hand: &BJHand, next: Option<&BJHand>, dealer_up_card: &Card,
+                  has_dealer_checked_bj: bool, bet: f64, settled: &Vec<(u32, f64)>, fixed: f64,
+                  payoff: Payoff) -> Vec<u8> {
+        let mut key = self.player_hand_hasher.hash_hand(&self.rules, hand);
+        match next {
+            Some(h) => {
+                key.push(1);
+                key.push_all(self.player_hand_hasher.hash_hand(&self.rules, h).as_slice());
+            }
+            None => key.push(0),
+        }
+        key.push_all(self.deck_hasher.hash_deck(&self.rules, &*self.shoe).as_slice());
+        key.push(dealer_up_card.value().index() as u8);
+        key.push(has_dealer_checked_bj as u8);
+        let (kind, parameter) = match payoff {
+            Payoff::Units => (0, 0.0),
+            Payoff::AtLeast(need) => (1, need),
+            Payoff::LossRebate(rebate) => (2, rebate),
+            Payoff::PromoChip(push) => (3, push),
+            Payoff::MatchPlay(push) => (4, push),
+        };
+        key.push(kind);
+        let mut amounts = vec![bet, fixed, parameter, self.extra_stake,
+                               self.max_extra_stake.unwrap_or(-1.0)];
+        for &(score, settled_bet) in settled.iter() {
+            amounts.push(score as f64);
+            amounts.push(settled_bet);
+        }
+        for a in amounts.iter() {
+            let hundredths = (*a * 100.0).round() as i64;
+            for i in range(0, 8) {
+                key.push((hundredths >> (8 * i)) as u8);
+            }
+        }
+        key
+    }
+
+    fn finish_distribution(&mut self, original_hand: &BJHand, dealer_up_card: &Card,
+                           has_dealer_checked_bj: bool, settled: &mut Vec<(u32, f64)>,
+                           fixed: f64) -> OutcomeDistribution {
+        if original_hand.splits_to_solve() > 0 {
+            // The cards of the hands already played stay out of the shoe
+            let mut hand = original_hand.create_next_split_hand();
+            return self.distribution_best_action(&mut hand, None, dealer_up_card,
+                                                 has_dealer_checked_bj, 1.0, settled, fixed);
+        }
+        // Under a dealer condition the dealer plays out even with nothing
+        // left to settle
+        if settled.len() == 0 && self.dealer_condition.is_none() {
+            return OutcomeDistribution::new_point(fixed);
+        }
+        let mut dealer_hand = BJHand::new();
+        dealer_hand.add_card(dealer_up_card);
+        let outcomes = self.dealer_outcomes(&mut dealer_hand);
+        let mut ret = OutcomeDistribution::new();
+        for (dealer_index, &odds) in outcomes.iter().enumerate() {
+            if self.dealer_condition.map_or(false, |d| d != dealer_index) {
+                continue;
+            }
+            let mut net = fixed;
+            for &(score, bet) in settled.iter() {
+                net += bet * stand_result(score, dealer_index, self.rules.dealer_wins_ties());
+            }
+            ret.add(net, odds);
+        }
+        ret
+    }
+}
+
+// 17 through 21, then bust
+pub const DEALER_OUTCOMES: usize = 6;
+
+pub fn dealer_outcome_index(dealer_score: u32) -> usize {
+    if dealer_score > 21 {
+        DEALER_OUTCOMES - 1
+    } else {
+        assert!(dealer_score >= 17);
+        dealer_score as usize - 17
+    }
+}
+
+// Units won by a standing player score against a final dealer outcome
+fn stand_result(player_score: u32, dealer_index: usize, dealer_wins_ties: bool) -> f64 {
+    if dealer_index == DEALER_OUTCOMES - 1 {
+        return 1.0;
+    }
+    let dealer_score = dealer_index as u32 + 17;
+    if dealer_score > player_score {
+        -1.0
+    } else if dealer_score < player_score {
+        1.0
+    } else if dealer_wins_ties {
+        -1.0
+    } else {
+        0.0
+    }
 }
 
 
@@ -465,7 +1368,11 @@ extern crate scope_time;
     use bjaction::BJAction::HIT;
     use rules::BJRules;
     use shoe::randomshoe::new_infinite_shoe;
+    use outcome::OutcomeDistribution;
+    use action_calculator::StrategyMode;
+    use shoe::composition::ShoeComposition;
     use cards::value::{TWO, THREE, FOUR, FIVE, SIX, SEVEN, EIGHT, NINE, TEN, ACE};
+    use hand::score_for_value;
 
     fn check_value(dealer_cards: &Vec<Value>, player_cards: &Vec<Value>,
                    expected: f64) {
@@ -942,6 +1849,253 @@ extern crate scope_time;
         }
     }
 
+    fn check_distribution(dealer_up_card: &Value, player_cards: &Vec<Value>,
+                          rules: &BJRules) -> (f64, OutcomeDistribution) {
+        let shoe = &mut new_infinite_shoe();
+        let player_hand = &mut BJHand::new_from_deck(shoe, player_cards).unwrap();
+        let dealer_up_card = &shoe.remove(dealer_up_card).unwrap();
+        let mut a = ActionCalculator::new(*rules, shoe);
+        let ev = a.expected_value_best_action(player_hand, dealer_up_card, true);
+        let d = a.outcome_distribution(player_hand, dealer_up_card, true);
+        assert!((d.total_probability() - 1.0).abs() < 0.000001);
+        (ev, d)
+    }
+
+    #[test]
+    fn test_outcome_distribution_matches_ev() {
+        let rules = BJRules::new();
+        let (ev, d) = check_distribution(&SEVEN, &vec![TEN, SEVEN], &rules);
+        assert!((ev - d.mean()).abs() < 0.000001);
+        assert!((-0.106809 - d.mean()).abs() < 0.000001);
+        let (ev, d) = check_distribution(&SIX, &vec![FIVE, SIX], &rules);
+        assert!((ev - d.mean()).abs() < 0.000001);
+        // Doubled, so only whole doubled bets change hands
+        assert_eq!(0.0, d.probability(1.0));
+        assert_eq!(0.0, d.probability(-1.0));
+        assert_eq!(3, d.outcomes().len());
+        assert!(d.standard_deviation() > 1.0);
+    }
+
+    #[test]
+    fn test_outcome_distribution_splits() {
+        let rules = BJRules::new_complex(false, 1, false, 1, false, false, true);
+        let (ev, d) = check_distribution(&TEN, &vec![EIGHT, EIGHT], &rules);
+        // Split hands share a dealer, so the extremes are more likely than
+        // the EV recursion's independent hands would suggest
+        assert!((ev - d.mean()).abs() < 0.01);
+        assert!(d.probability(-2.0) > 0.0);
+        assert!(d.probability(4.0) > 0.0);
+        assert_eq!(0.0, d.probability(5.0));
+    }
+
+    #[test]
+    fn test_strategy_modes_infinite_shoe() {
+        // Every hand with the same total has the same odds, so knowing the
+        // cards is worth nothing
+        let shoe = &mut new_infinite_shoe();
+        let mut a = ActionCalculator::new(BJRules::new(), shoe);
+        let values = a.strategy_mode_values();
+        assert_eq!(3, values.len());
+        for &(_, ev) in values.iter() {
+            assert!((ev - values[2].1).abs() < 0.0000001);
+        }
+    }
+
+    #[test]
+    fn test_strategy_modes_small_shoe() {
+        let mut cards = Vec::new();
+        let shoe = &mut ShoeComposition::new_from_counts([2, 2, 2, 2, 2, 2, 2, 2, 2, 8])
+            .direct_shoe(&mut cards);
+        let mut a = ActionCalculator::new(BJRules::new(), shoe);
+        let values = a.strategy_mode_values();
+        assert_eq!(StrategyMode::TotalDependent, values[0].0);
+        assert_eq!(StrategyMode::CompositionDependent, values[2].0);
+        assert!(values[0].1 <= values[1].1 + 0.0000001);
+        assert!(values[1].1 <= values[2].1 + 0.0000001);
+        // The calculator goes back to the best play for the exact cards
+        assert!((a.total_expected_best_value() - values[2].1).abs() < 0.0000001);
+    }
+
+    #[test]
+    fn test_two_card_mode_keeps_hand_sizes_apart() {
+        use std::collections::HashMap;
+        // Without doubles or splits, 10,2 and 5,4,3 have the same actions
+        let rules = BJRules::new_complex(false, 0, false, 0, false, false, false);
+        let shoe = &mut new_infinite_shoe();
+        let two = &mut BJHand::new_from_deck(shoe, &vec![TEN, TWO]).unwrap();
+        let three = &mut BJHand::new_from_deck(shoe, &vec![FIVE, FOUR, THREE]).unwrap();
+        let six = shoe.remove(&SIX).unwrap();
+        let mut a = ActionCalculator::new(rules, shoe);
+        let mut table = HashMap::new();
+        table.insert(a.strategy_key(three, &six), HIT);
+        a.set_strategy(StrategyMode::TwoCardCompositionDependent, table);
+        // The table hits the three card 12, the two card 12 stands
+        let hit = a.expected_value_best_action(three, &six, true);
+        let stand = a.expected_value_best_action(two, &six, true);
+        assert!((stand - -0.153699).abs() < 0.000001);
+        assert!(hit < stand);
+    }
+
+    #[test]
+    fn test_pontoon_cards_toward_a_trick() {
+        let mut rules = BJRules::new();
+        rules.set_pontoon(true);
+        let shoe = &mut new_infinite_shoe();
+        let three = &mut BJHand::new_from_deck(shoe, &vec![FIVE, FOUR, THREE]).unwrap();
+        let four = &mut BJHand::new_from_deck(shoe, &vec![THREE, THREE, THREE, THREE]).unwrap();
+        let ten = shoe.remove(&TEN).unwrap();
+        let mut a = ActionCalculator::new(rules, shoe);
+        // Any card that doesn't bust the four card 12 makes a five card trick
+        let three_hit = a.expected_value(three, &ten, HIT, true).unwrap();
+        let four_hit = a.expected_value(four, &ten, HIT, true).unwrap();
+        assert!(four_hit > three_hit + 0.1);
+    }
+
+    #[test]
+    fn test_hole_card_hint() {
+        use hole_card::HoleCardHint;
+        let shoe = &mut new_infinite_shoe();
+        let hand = &mut BJHand::new_from_deck(shoe, &vec![TEN, TEN]).unwrap();
+        let six = shoe.remove(&SIX).unwrap();
+        let ace = shoe.remove(&ACE).unwrap();
+        let mut a = ActionCalculator::new(BJRules::new(), shoe);
+        a.set_hole_card_hint(HoleCardHint::new_ten_value());
+        // The dealer has 16 and busts on 6 through K, makes 17 to 19 with A
+        // through 3, pushes with a 4 and beats 20 with a 5
+        let stand = a.expected_value(hand, &six, STAND, true).unwrap();
+        assert!((stand - 10.0 / 13.0).abs() < 0.000001);
+        // A ten under an ace is a blackjack
+        assert_eq!(-1.0, a.expected_value_best_action(hand, &ace, false));
+        a.set_hole_card_hint(HoleCardHint::new_unknown());
+        let stand = a.expected_value(hand, &six, STAND, true).unwrap();
+        assert!((stand - 0.703959).abs() < 0.000001);
+    }
+
+    #[test]
+    fn test_double_exposure() {
+        use hole_card::HoleCardHint;
+        let mut rules = BJRules::new();
+        rules.set_double_exposure(true, false);
+        let shoe = &mut new_infinite_shoe();
+        let hand = &mut BJHand::new_from_deck(shoe, &vec![TEN, TEN]).unwrap();
+        let six = shoe.remove(&SIX).unwrap();
+        let ten = shoe.remove(&TEN).unwrap();
+        let mut a = ActionCalculator::new(rules, shoe);
+        a.set_hole_card_hint(HoleCardHint::new_exact(&TEN));
+        // 20 against 20 loses
+        assert_eq!(-1.0, a.expected_value(hand, &ten, STAND, true).unwrap());
+        // Against 16 the 4 that made a push now makes the dealer 20 too
+        let stand = a.expected_value(hand, &six, STAND, true).unwrap();
+        assert!((stand - 9.0 / 13.0).abs() < 0.000001);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_double_exposure_edge() {
+        // Seeing the hole card doesn't make up for losing the ties
+        let mut rules = BJRules::new();
+        rules.set_double_exposure(true, false);
+        let shoe = &mut new_infinite_shoe();
+        let mut a = ActionCalculator::new(rules, shoe);
+        let ev = a.total_expected_best_value();
+        assert!(ev < 0.0);
+    }
+
+    #[test]
+    fn test_double_attack() {
+        let mut plain = BJRules::new_complex(true, 3, false, 1, false, false, true);
+        plain.set_blackjack_payout(1.0);
+        let mut rules = plain;
+        rules.set_double_attack(true);
+        let shoe = &mut ShoeComposition::new_spanish_decks(1).infinite_shoe();
+        let plain_ev = ActionCalculator::new(plain, shoe).total_expected_best_value();
+        let mut a = ActionCalculator::new(rules, shoe);
+        let values = a.double_attack_values();
+        assert_eq!(10, values.len());
+        // Never raising is the same game without the raise
+        let flat_ev = values.iter().fold(0.0, |sum, &(_, odds, ev, _)| sum + odds * ev);
+        assert!((flat_ev - plain_ev).abs() < 0.0000001);
+        for &(up, _, ev, raised_ev) in values.iter() {
+            match score_for_value(&up) {
+                5 | 6 => assert!(raised_ev > ev),
+                1 | 10 => assert!(raised_ev < ev),
+                _ => {}
+            }
+        }
+        assert!(a.total_expected_best_value() > plain_ev);
+    }
+
+    #[test]
+    fn test_tournament() {
+        let shoe = &mut new_infinite_shoe();
+        let mut a = ActionCalculator::new(BJRules::new(), shoe);
+        // Doubling chips from 200 to 400 on a bet of 100 takes 2 units, which
+        // standing on 20 can't win
+        let values = a.tournament_action_values(&vec![TEN, TEN], &SIX, 200.0, 100.0,
+                                                400.0).unwrap();
+        assert_eq!(0.0, values.iter().find(|x| x.0 == STAND).unwrap().1);
+        let best = values.iter().fold(values[0], |b, x| if x.1 > b.1 { *x } else { b });
+        // Splitting needs both tens to win, doubling needs an ace
+        assert!(best.0 == BJAction::SPLIT);
+        assert!(best.1 > 0.2);
+        // Betting the whole stack leaves nothing to split or double with
+        let values = a.tournament_action_values(&vec![TEN, TEN], &SIX, 100.0, 100.0,
+                                                300.0).unwrap();
+        assert!(values.iter().all(|x| x.0 != BJAction::SPLIT && x.0 != BJAction::DOUBLE));
+        assert!(values.iter().all(|x| x.1 == 0.0));
+        let values = a.tournament_action_values(&vec![TEN, TEN], &SIX, 100.0, 0.0,
+                                                300.0).unwrap();
+        assert!(values.iter().all(|x| x.1 == 0.0));
+        // Playing for the target does at least as well as playing for EV,
+        // with chips enough for every double and split
+        let ev_odds = a.total_outcome_distribution().probability_at_least(1.0);
+        let odds = a.tournament_odds(1000.0, 100.0, 1100.0);
+        assert!(odds >= ev_odds - 0.0000001);
+        assert!(odds < 0.5);
+        assert_eq!(1.0, a.tournament_odds(200.0, 0.0, 200.0));
+        // Reaching 150 from 100 only takes a win with the whole stack
+        let (bet, best_odds) = a.tournament_best_bet(100.0, 150.0, &[25.0, 50.0, 100.0]).unwrap();
+        assert!(bet >= 50.0);
+        assert!((best_odds - a.tournament_odds(100.0, bet, 150.0)).abs() < 0.0000001);
+    }
+
+    #[test]
+    fn test_loss_rebate() {
+        use payoff::Payoff;
+        let rules = BJRules::new_complex(true, 3, false, 1, false, false, true);
+        let shoe = &mut new_infinite_shoe();
+        let mut a = ActionCalculator::new(rules, shoe);
+        let best = |values: &Vec<(BJAction, f64)>| {
+            values.iter().fold(values[0], |b, x| if x.1 > b.1 { *x } else { b })
+        };
+        let units = a.payoff_action_values(&vec![TEN, SIX], &TEN, Payoff::Units).unwrap();
+        assert!(best(&units).0 == BJAction::SURRENDER);
+        let evs = a.action_values(&vec![TEN, SIX], &TEN).unwrap();
+        let stand_ev = evs.iter().find(|x| x.0 == STAND).unwrap().1;
+        assert!((units.iter().find(|x| x.0 == STAND).unwrap().1 - stand_ev).abs() < 0.000001);
+        // Getting half of every loss back makes giving up half the bet poor
+        let rebate = a.payoff_action_values(&vec![TEN, SIX], &TEN,
+                                            Payoff::LossRebate(0.5)).unwrap();
+        assert_eq!(-0.25, rebate.iter().find(|x| x.0 == BJAction::SURRENDER).unwrap().1);
+        assert!(best(&rebate).0 != BJAction::SURRENDER);
+    }
+
+    #[test]
+    fn test_multiple_hands() {
+        let shoe = &mut new_infinite_shoe();
+        let mut a = ActionCalculator::new(BJRules::new(), shoe);
+        let two = a.multiple_hands_infinite_deck(2);
+        let d = a.total_outcome_distribution();
+        assert!((d.total_probability() - 1.0).abs() < 0.000001);
+        assert!((two.ev_per_hand() - d.mean()).abs() < 0.000001);
+        assert!((two.variance_per_hand() - d.variance()).abs() < 0.000001);
+        // Hands against the same dealer tend to win and lose together
+        assert!(two.covariance() > 0.0);
+        assert!(two.correlation() < 1.0);
+        assert!((two.ev() - 2.0 * d.mean()).abs() < 0.000001);
+        assert!(two.variance() > 2.0 * d.variance());
+    }
+
     #[bench]
     fn bench_with_calc(b: &mut Bencher) {
         use cards::value;
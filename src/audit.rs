@@ -0,0 +1,295 @@
+use std::fmt;
+use action_calculator::ActionCalculator;
+use bjaction::BJAction;
+use bjaction::BJAction::{STAND, DOUBLE, SPLIT, SURRENDER};
+use cards::card::Card;
+use hand::BJHand;
+use history::{HandHistory, HistoryError, Player, RoundRecord, play_round, replay_round};
+use round::Round;
+use shoe::directshoe::DirectActualShoe;
+use strategy_export::best_with_ev;
+
+// Losses smaller than this are rounding, not mistakes
+const MISTAKE_THRESHOLD: f64 = 1e-9;
+
+#[derive(Copy, Clone, PartialEq, Show)]
+pub enum DecisionCategory {
+    Insurance,
+    Surrender,
+    Split,
+    SoftDouble,
+    HardDouble,
+    HitStand,
+}
+
+pub const DECISION_CATEGORIES: [DecisionCategory; 6] = [DecisionCategory::Insurance,
+    DecisionCategory::Surrender, DecisionCategory::Split, DecisionCategory::SoftDouble,
+    DecisionCategory::HardDouble, DecisionCategory::HitStand];
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Choice {
+    Insurance(bool),
+    Action(BJAction),
+}
+
+impl fmt::Show for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Choice::Insurance(true) => "INS".fmt(f),
+            Choice::Insurance(false) => "NOINS".fmt(f),
+            Choice::Action(a) => a.fmt(f),
+        }
+    }
+}
+
+/**
+  One decision from a history with what it was worth.  EVs are in units of
+  money, so a mistake on a doubled or split hand counts for its whole wager.
+  */
+pub struct AuditedDecision {
+    // Index into the history's rounds
+    pub round: usize,
+    pub hand: BJHand,
+    pub dealer_up_card: Card,
+    pub category: DecisionCategory,
+    pub chosen: Choice,
+    pub chosen_ev: f64,
+    pub best: Choice,
+    pub best_ev: f64,
+}
+
+impl AuditedDecision {
+    pub fn ev_lost(&self) -> f64 {
+        self.best_ev - self.chosen_ev
+    }
+
+    pub fn is_mistake(&self) -> bool {
+        self.ev_lost() > MISTAKE_THRESHOLD
+    }
+}
+
+impl fmt::Show for AuditedDecision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cards: Vec<String> = self.hand.cards().iter().map(|c| format!("{:?}", c)).collect();
+        write!(f, "round {} {} vs {:?}: {:?} {:+.4}, best {:?} {:+.4}, lost {:.4}",
+               self.round + 1, cards.concat(), self.dealer_up_card, self.chosen,
+               self.chosen_ev, self.best, self.best_ev, self.ev_lost())
+    }
+}
+
+pub struct AuditReport {
+    pub decisions: Vec<AuditedDecision>,
+}
+
+impl AuditReport {
+    pub fn total_ev_lost(&self) -> f64 {
+        self.decisions.iter().map(|d| d.ev_lost()).fold(0.0, |a, b| a + b)
+    }
+
+    pub fn mistakes(&self) -> Vec<&AuditedDecision> {
+        self.decisions.iter().filter(|d| d.is_mistake()).collect()
+    }
+
+    // How many decisions, how many mistakes and the EV lost in the category
+    pub fn category_summary(&self, category: DecisionCategory) -> (usize, usize, f64) {
+        let mut ret = (0, 0, 0.0);
+        for d in self.decisions.iter().filter(|d| d.category == category) {
+            ret.0 += 1;
+            if d.is_mistake() {
+                ret.1 += 1;
+            }
+            ret.2 += d.ev_lost();
+        }
+        ret
+    }
+}
+
+impl fmt::Show for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for d in self.mistakes().iter() {
+            try!(writeln!(f, "{:?}", d));
+        }
+        try!(writeln!(f, "{:<12}{:>10}{:>10}{:>10}", "category", "decisions", "mistakes", "ev_lost"));
+        for c in DECISION_CATEGORIES.iter() {
+            let (decisions, mistakes, lost) = self.category_summary(*c);
+            try!(writeln!(f, "{:<12}{:>10}{:>10}{:>10.4}", format!("{:?}", c), decisions,
+                          mistakes, lost));
+        }
+        writeln!(f, "{:<12}{:>10}{:>10}{:>10.4}", "Total", self.decisions.len(),
+                 self.mistakes().len(), self.total_ev_lost())
+    }
+}
+
+fn category(hand: &BJHand, chosen: BJAction, best: BJAction) -> DecisionCategory {
+    let either = |a| chosen == a || best == a;
+    if either(SURRENDER) {
+        DecisionCategory::Surrender
+    } else if either(SPLIT) {
+        DecisionCategory::Split
+    } else if either(DOUBLE) {
+        if hand.is_soft() { DecisionCategory::SoftDouble } else { DecisionCategory::HardDouble }
+    } else {
+        DecisionCategory::HitStand
+    }
+}
+
+// Makes the recorded decisions, scoring each one as it goes
+struct AuditPlayer<'a> {
+    record: &'a RoundRecord,
+    round: usize,
+    next: usize,
+    decisions: Vec<AuditedDecision>,
+}
+
+impl <'a>Player for AuditPlayer<'a> {
+    fn insurance(&mut self, round: &Round) -> bool {
+        let take = self.record.insurance.unwrap_or(false);
+        let insured_ev = round.insurance_ev(round.insurance_seat().unwrap());
+        let (best, best_ev) = if insured_ev > 0.0 { (true, insured_ev) } else { (false, 0.0) };
+        self.decisions.push(AuditedDecision {
+            round: self.round,
+            hand: round.hands()[0].hand().clone(),
+            dealer_up_card: *round.dealer_up_card(),
+            category: DecisionCategory::Insurance,
+            chosen: Choice::Insurance(take),
+            chosen_ev: if take { insured_ev } else { 0.0 },
+            best: Choice::Insurance(best),
+            best_ev: best_ev,
+        });
+        take
+    }
+
+    fn action(&mut self, round: &Round) -> BJAction {
+        let chosen = self.record.actions.get(self.next).map(|a| *a).unwrap_or(STAND);
+        self.next += 1;
+        let player_hand = round.active_hand().unwrap();
+        let up = *round.dealer_up_card();
+        let mut cards = Vec::new();
+        let mut shoe = round.unseen_composition().direct_shoe(&mut cards);
+        let mut calc = ActionCalculator::new(*round.rules(), &mut shoe);
+        let mut hand = player_hand.hand().clone();
+        let mut evs = Vec::new();
+        for a in round.legal_actions().iter() {
+            match calc.expected_value(&mut hand, &up, *a, true) {
+                Some(ev) => evs.push((*a, ev * player_hand.wager())),
+                None => {}
+            }
+        }
+        match (best_with_ev(&evs), evs.iter().find(|x| x.0 == chosen)) {
+            (Some(b), Some(c)) => self.decisions.push(AuditedDecision {
+                round: self.round,
+                hand: hand.clone(),
+                dealer_up_card: up,
+                category: category(&hand, chosen, b.0),
+                chosen: Choice::Action(chosen),
+                chosen_ev: c.1,
+                best: Choice::Action(b.0),
+                best_ev: b.1,
+            }),
+            _ => {}
+        }
+        chosen
+    }
+}
+
+/**
+  Scores every decision in the history against the best one for the cards
+  the player couldn't see.  The shoe is the rule set's decks, 6 if it
+  doesn't say and Spanish for Double Attack, less every card recorded since
+  the last reshuffle: a round whose shoe position isn't past the one before
+  it starts a new shoe.  Cards dealt between recorded rounds aren't known,
+  so they're left in.
+  */
+pub fn audit(history: &HandHistory) -> Result<AuditReport, HistoryError> {
+    let full = history.rule_set.composition(6);
+    let mut remaining = full;
+    let mut last_position = None;
+    let mut ret = AuditReport {
+        decisions: Vec::new(),
+    };
+    for (i, record) in history.rounds.iter().enumerate() {
+        let line = history.round_line(i);
+        try!(replay_round(&history.rule_set, record).map_err(
+            |e| HistoryError::new(line, e.as_slice())));
+        match last_position {
+            Some(p) if record.shoe_position > p => {}
+            _ => remaining = full,
+        }
+        last_position = Some(record.shoe_position);
+        for c in record.cards.iter() {
+            if remaining.count(c.value()) == 0 {
+                return Err(HistoryError::new(line, format!(
+                    "more {:?}s than the shoe holds", c.value()).as_slice()));
+            }
+            remaining.remove(c.value(), 1);
+        }
+        // The rest of the shoe sits under the recorded cards
+        let mut cards = remaining.cards();
+        cards.extend(record.cards.iter().rev().map(|c| *c));
+        let mut shoe = DirectActualShoe {
+            initial_length: Some(full.len()),
+            maximum_count_of_any_value: Some(full.max_count()),
+            cards: &mut cards,
+        };
+        let mut player = AuditPlayer {
+            record: record,
+            round: i,
+            next: 0,
+            decisions: Vec::new(),
+        };
+        try!(play_round(&history.rule_set, &mut shoe, record.wager, &mut player).map_err(
+            |e| HistoryError::new(line, format!("{:?}", e).as_slice())));
+        ret.decisions.extend(player.decisions.into_iter());
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use audit::{audit, Choice, DecisionCategory};
+    use bjaction::BJAction::{HIT, STAND};
+    use history::HandHistory;
+
+    #[test]
+    fn test_audit() {
+        let history = HandHistory::parse("\
+            rules 6D S17\n\
+            round pos=0 wager=1 cards=Ts6hTdTc2s insurance=- actions=HIT result=-1\n\
+            round pos=5 wager=1 cards=Ts5hTdKc9s insurance=- actions=STD result=1\n\
+            round pos=10 wager=2 cards=TsAh9d7c insurance=n actions=STD result=2\n").unwrap();
+        let report = audit(&history).unwrap();
+        assert_eq!(4, report.decisions.len());
+        let mistakes = report.mistakes();
+        assert_eq!(1, mistakes.len());
+        // Hitting 20 against a 6
+        assert_eq!(0, mistakes[0].round);
+        assert!(mistakes[0].chosen == Choice::Action(HIT));
+        assert!(mistakes[0].best == Choice::Action(STAND));
+        assert_eq!(DecisionCategory::HitStand, mistakes[0].category);
+        assert!(mistakes[0].ev_lost() > 1.0);
+        assert_eq!(mistakes[0].ev_lost(), report.total_ev_lost());
+        // Turning down insurance with fewer than a third tens left
+        assert_eq!((1, 0, 0.0), report.category_summary(DecisionCategory::Insurance));
+        assert_eq!(3, report.category_summary(DecisionCategory::HitStand).0);
+    }
+
+    #[test]
+    fn test_audit_needs_cards_in_the_shoe() {
+        // A fifth ace from one deck
+        let history = HandHistory::parse("\
+            rules 1D S17\n\
+            round pos=0 wager=1 cards=AsTsKdTc insurance=- actions=- result=1.5\n\
+            round pos=4 wager=1 cards=AsTsKdTc insurance=- actions=- result=1.5\n\
+            round pos=8 wager=1 cards=AsTsKdTc insurance=- actions=- result=1.5\n\
+            round pos=12 wager=1 cards=AsTsKdTc insurance=- actions=- result=1.5\n\
+            round pos=16 wager=1 cards=AsTsKdTc insurance=- actions=- result=1.5\n").unwrap();
+        assert_eq!(6, audit(&history).err().unwrap().line);
+        // The line in the file, not the round's place in it
+        let history = HandHistory::parse("\
+            # One deck\n\
+            rules 1D S17\n\
+            \n\
+            round pos=0 wager=1 cards=Ts6hTdTc2s insurance=- actions=HIT result=1\n").unwrap();
+        assert_eq!(4, audit(&history).err().unwrap().line);
+    }
+}
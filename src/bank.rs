@@ -0,0 +1,414 @@
+use std::f64::consts::PI;
+use std::num::Float;
+use std::rand::Rng;
+use outcome::OutcomeDistribution;
+use shoe::shuffle::seeded_rng;
+
+/**
+  Chance of ever losing the whole bankroll playing forever with a per round
+  EV and variance.  The bankroll is in the same units as the EV.
+  */
+pub fn risk_of_ruin(ev: f64, variance: f64, bankroll: f64) -> f64 {
+    if ev <= 0.0 {
+        return 1.0;
+    }
+    (-2.0 * ev * bankroll / variance).exp()
+}
+
+// The bankroll needed to keep the risk of ruin at ror
+pub fn bankroll_for_risk(ev: f64, variance: f64, ror: f64) -> Option<f64> {
+    if ev <= 0.0 {
+        return None;
+    }
+    Some(-variance * ror.ln() / (2.0 * ev))
+}
+
+// Fraction of the bankroll to bet on a single round
+pub fn kelly_fraction(ev: f64, variance: f64) -> f64 {
+    if ev <= 0.0 {
+        0.0
+    } else {
+        ev / variance
+    }
+}
+
+// fraction is 1.0 for full Kelly, 0.5 for half Kelly ...
+pub fn kelly_bet(ev: f64, variance: f64, bankroll: f64, fraction: f64) -> f64 {
+    bankroll * fraction * kelly_fraction(ev, variance)
+}
+
+// Rounds needed before the EV overcomes one standard deviation
+pub fn n0(ev: f64, variance: f64) -> Option<f64> {
+    if ev <= 0.0 {
+        None
+    } else {
+        Some(variance / (ev * ev))
+    }
+}
+
+/**
+  How often a situation (usually a true count) comes up, how much is bet on
+  it and the EV and variance of a one unit bet there.
+  */
+#[derive(Clone)]
+pub struct BetLevel {
+    frequency: f64,
+    bet: f64,
+    ev: f64,
+    variance: f64,
+    // When known, simulations draw results from this instead of a normal curve
+    outcomes: Option<OutcomeDistribution>,
+}
+
+impl BetLevel {
+    pub fn new(frequency: f64, bet: f64, ev: f64, variance: f64) -> BetLevel {
+        BetLevel {
+            frequency: frequency,
+            bet: bet,
+            ev: ev,
+            variance: variance,
+            outcomes: None,
+        }
+    }
+
+    pub fn new_from_outcomes(frequency: f64, bet: f64, outcomes: &OutcomeDistribution) -> BetLevel {
+        BetLevel {
+            frequency: frequency,
+            bet: bet,
+            ev: outcomes.mean(),
+            variance: outcomes.variance(),
+            outcomes: Some(outcomes.clone()),
+        }
+    }
+
+    pub fn frequency(&self) -> f64 {
+        self.frequency
+    }
+
+    pub fn bet(&self) -> f64 {
+        self.bet
+    }
+
+    pub fn ev(&self) -> f64 {
+        self.ev
+    }
+
+    pub fn variance(&self) -> f64 {
+        self.variance
+    }
+
+    pub fn with_bet(&self, bet: f64) -> BetLevel {
+        let mut ret = self.clone();
+        ret.bet = bet;
+        ret
+    }
+
+    // Result of a one unit bet
+    fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match self.outcomes {
+            Some(ref d) => {
+                let mut pick = rng.gen::<f64>() * d.total_probability();
+                let outcomes = d.outcomes();
+                for &(units, p) in outcomes.iter() {
+                    if pick < p {
+                        return units;
+                    }
+                    pick -= p;
+                }
+                outcomes[outcomes.len() - 1].0
+            }
+            None => {
+                // Box-Muller
+                let u1 = 1.0 - rng.gen::<f64>();
+                let u2 = rng.gen::<f64>();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+                self.ev + z * self.variance.sqrt()
+            }
+        }
+    }
+}
+
+/**
+  The bets made across every situation of a game.  Frequencies don't have to
+  add to one: they're normalized.
+  */
+#[derive(Clone)]
+pub struct BetSpread {
+    levels: Vec<BetLevel>,
+}
+
+impl BetSpread {
+    pub fn new(levels: Vec<BetLevel>) -> BetSpread {
+        assert!(levels.len() > 0);
+        BetSpread {
+            levels: levels,
+        }
+    }
+
+    // One unit bet every round
+    pub fn new_flat(ev: f64, variance: f64) -> BetSpread {
+        BetSpread::new(vec![BetLevel::new(1.0, 1.0, ev, variance)])
+    }
+
+    pub fn levels(&self) -> &Vec<BetLevel> {
+        &self.levels
+    }
+
+    fn total_frequency(&self) -> f64 {
+        self.levels.iter().fold(0.0, |sum, l| sum + l.frequency)
+    }
+
+    pub fn average_bet(&self) -> f64 {
+        self.levels.iter().fold(0.0, |sum, l| sum + l.frequency * l.bet) / self.total_frequency()
+    }
+
+    // Largest bet over smallest bet
+    pub fn spread(&self) -> f64 {
+        let bets: Vec<f64> = self.levels.iter().map(|l| l.bet).filter(|b| *b > 0.0).collect();
+        let max = bets.iter().fold(0.0, |m, b| if *b > m { *b } else { m });
+        let min = bets.iter().fold(max, |m, b| if *b < m { *b } else { m });
+        max / min
+    }
+
+    // Units won per round
+    pub fn win_rate(&self) -> f64 {
+        self.levels.iter().fold(0.0, |sum, l| sum + l.frequency * l.bet * l.ev) / self.total_frequency()
+    }
+
+    pub fn variance(&self) -> f64 {
+        let second_moment = self.levels.iter().fold(
+            0.0, |sum, l| sum + l.frequency * l.bet * l.bet * (l.variance + l.ev * l.ev)) /
+            self.total_frequency();
+        second_moment - self.win_rate() * self.win_rate()
+    }
+
+    pub fn standard_deviation(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    pub fn n0(&self) -> Option<f64> {
+        n0(self.win_rate(), self.variance())
+    }
+
+    // Win per 100 rounds betting full Kelly with a 10,000 unit bankroll
+    pub fn score(&self) -> f64 {
+        match self.n0() {
+            Some(n) => 1000000.0 / n,
+            None => 0.0,
+        }
+    }
+
+    pub fn risk_of_ruin(&self, bankroll: f64) -> f64 {
+        risk_of_ruin(self.win_rate(), self.variance(), bankroll)
+    }
+
+    pub fn bankroll_for_risk(&self, ror: f64) -> Option<f64> {
+        bankroll_for_risk(self.win_rate(), self.variance(), ror)
+    }
+
+    /**
+      The same situations bet with fractional Kelly for the bankroll.  Bets
+      never drop below min_bet, which is what's bet whenever the player has
+      no edge.
+      */
+    pub fn kelly(&self, bankroll: f64, fraction: f64, min_bet: f64) -> BetSpread {
+        BetSpread::new(self.levels.iter().map(|l| {
+            let bet = kelly_bet(l.ev, l.variance, bankroll, fraction);
+            l.with_bet(if bet < min_bet { min_bet } else { bet })
+        }).collect())
+    }
+
+    /**
+      Plays trials sessions of up to rounds rounds each, returning the part
+      of them that lost the whole bankroll.
+      */
+    pub fn simulated_risk_of_ruin(&self, bankroll: f64, rounds: u32, trials: u32,
+                                  seed: u32) -> f64 {
+        let mut rng = seeded_rng(seed);
+        let total_frequency = self.total_frequency();
+        let mut ruined = 0;
+        for _ in range(0, trials) {
+            let mut bank = bankroll;
+            for _ in range(0, rounds) {
+                let mut pick = rng.gen::<f64>() * total_frequency;
+                let mut level = &self.levels[self.levels.len() - 1];
+                for l in self.levels.iter() {
+                    if pick < l.frequency {
+                        level = l;
+                        break;
+                    }
+                    pick -= l.frequency;
+                }
+                bank += level.bet * level.sample(&mut rng);
+                if bank <= 0.0 {
+                    ruined += 1;
+                    break;
+                }
+            }
+        }
+        ruined as f64 / trials as f64
+    }
+}
+
+/**
+  Several one unit hands played at once against the same dealer hand.  They
+  win and lose together through the dealer's hand, so every pair of hands
+  adds their covariance to the round's variance.
+  */
+#[derive(Copy, Clone)]
+pub struct MultipleHands {
+    hands: u32,
+    // Of one hand
+    ev: f64,
+    variance: f64,
+    // Between any two hands
+    covariance: f64,
+}
+
+impl MultipleHands {
+    pub fn new(hands: u32, ev: f64, variance: f64, covariance: f64) -> MultipleHands {
+        assert!(hands > 0);
+        MultipleHands {
+            hands: hands,
+            ev: ev,
+            variance: variance,
+            covariance: covariance,
+        }
+    }
+
+    pub fn with_hands(&self, hands: u32) -> MultipleHands {
+        MultipleHands::new(hands, self.ev, self.variance, self.covariance)
+    }
+
+    pub fn hands(&self) -> u32 {
+        self.hands
+    }
+
+    pub fn ev_per_hand(&self) -> f64 {
+        self.ev
+    }
+
+    pub fn variance_per_hand(&self) -> f64 {
+        self.variance
+    }
+
+    pub fn covariance(&self) -> f64 {
+        self.covariance
+    }
+
+    pub fn correlation(&self) -> f64 {
+        if self.variance == 0.0 { 0.0 } else { self.covariance / self.variance }
+    }
+
+    // Units won per round
+    pub fn ev(&self) -> f64 {
+        self.hands as f64 * self.ev
+    }
+
+    pub fn variance(&self) -> f64 {
+        let n = self.hands as f64;
+        n * self.variance + n * (n - 1.0) * self.covariance
+    }
+
+    pub fn standard_deviation(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    pub fn n0(&self) -> Option<f64> {
+        n0(self.ev(), self.variance())
+    }
+
+    // Win per 100 rounds betting full Kelly on each hand with a 10,000 unit
+    // bankroll.  Never lower with more hands, since the bets shrink to match
+    pub fn score(&self) -> f64 {
+        match self.n0() {
+            Some(n) => 1000000.0 / n,
+            None => 0.0,
+        }
+    }
+
+    // The bet on each hand
+    pub fn kelly_bet(&self, bankroll: f64, fraction: f64) -> f64 {
+        kelly_bet(self.ev(), self.variance(), bankroll, fraction)
+    }
+
+    /**
+      What a round of one unit hands is worth to a Kelly bettor with the
+      bankroll: the EV less half the variance over the bankroll.
+      */
+    pub fn certainty_equivalent(&self, bankroll: f64) -> f64 {
+        self.ev() - self.variance() / (2.0 * bankroll)
+    }
+
+    // The number of hands up to max_hands with the best certainty equivalent
+    pub fn best_hands(&self, max_hands: u32, bankroll: f64) -> MultipleHands {
+        let mut best = self.with_hands(1);
+        for n in range(2, max_hands + 1) {
+            let m = self.with_hands(n);
+            if m.certainty_equivalent(bankroll) > best.certainty_equivalent(bankroll) {
+                best = m;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bank::{risk_of_ruin, bankroll_for_risk, kelly_bet, n0, BetLevel, BetSpread, MultipleHands};
+    use std::num::Float;
+
+    #[test]
+    fn test_flat_betting() {
+        // 1% edge, variance 1.3 and 100 units
+        let ror = risk_of_ruin(0.01, 1.3, 100.0);
+        assert!((ror - 0.214711).abs() < 0.000001);
+        assert!((bankroll_for_risk(0.01, 1.3, ror).unwrap() - 100.0).abs() < 0.000001);
+        assert_eq!(1.0, risk_of_ruin(-0.005, 1.3, 1000.0));
+        assert!((n0(0.01, 1.3).unwrap() - 13000.0).abs() < 0.000001);
+        assert!(n0(0.0, 1.3).is_none());
+        assert!((kelly_bet(0.01, 1.3, 10000.0, 0.5) - 38.461538).abs() < 0.000001);
+
+        let flat = BetSpread::new_flat(0.01, 1.3);
+        assert!((flat.risk_of_ruin(100.0) - ror).abs() < 0.000001);
+    }
+
+    #[test]
+    fn test_count_spread() {
+        let spread = BetSpread::new(vec![
+            BetLevel::new(0.7, 1.0, -0.005, 1.3),
+            BetLevel::new(0.2, 4.0, 0.01, 1.3),
+            BetLevel::new(0.1, 8.0, 0.02, 1.3),
+        ]);
+        assert_eq!(8.0, spread.spread());
+        assert!((spread.average_bet() - 2.3).abs() < 0.000001);
+        assert!((spread.win_rate() - 0.0205).abs() < 0.000001);
+        assert!(spread.variance() > 1.3);
+
+        let kelly = spread.kelly(1000.0, 1.0, 1.0);
+        assert_eq!(1.0, kelly.levels()[0].bet());
+        assert!((kelly.levels()[2].bet() - 1000.0 * 0.02 / 1.3).abs() < 0.000001);
+    }
+
+    #[test]
+    fn test_simulated_risk_of_ruin() {
+        let flat = BetSpread::new_flat(0.01, 1.3);
+        // Long sessions get close to the analytic answer
+        let simulated = flat.simulated_risk_of_ruin(20.0, 20000, 400, 5);
+        assert!((simulated - flat.risk_of_ruin(20.0)).abs() < 0.1);
+        assert_eq!(simulated, flat.simulated_risk_of_ruin(20.0, 20000, 400, 5));
+    }
+
+    #[test]
+    fn test_multiple_hands() {
+        let one = MultipleHands::new(1, 0.02, 1.3, 0.5);
+        let two = one.with_hands(2);
+        assert!((two.ev() - 0.04).abs() < 0.000001);
+        assert!((two.variance() - 3.6).abs() < 0.000001);
+        assert!(two.score() > one.score());
+        assert!((two.kelly_bet(100.0, 1.0) - 100.0 * 0.04 / 3.6).abs() < 0.000001);
+        // A third hand still pays for its risk with 100 units, a fourth doesn't
+        assert_eq!(3, one.best_hands(6, 100.0).hands());
+        assert_eq!(1, one.best_hands(6, 10.0).hands());
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::num::Float;
+use action_calculator::ActionCalculator;
+use bank::BetLevel;
+use bank::BetSpread;
+use cardshoetracker::CardShoeTracker;
+use counting::CountingSystem;
+use rules::BJRules;
+use shoe::shoe::DirectShoe;
+use shoe::deck::cards_in_deck;
+use shoe::cutcardshoe::CutCardShoe;
+use shoe::composition::ShoeComposition;
+use strategy_export::deplete_to_true_count;
+
+// Cards a heads up round uses, on average
+const CARDS_PER_ROUND: u32 = 5;
+
+/**
+  How often a true count comes up, and the EV and variance of a one unit bet
+  played with the best strategy at that count.
+  */
+pub struct CountStats {
+    pub true_count: i32,
+    pub frequency: f64,
+    pub ev: f64,
+    pub variance: f64,
+}
+
+/**
+  Deals rounds from a cut card shoe, tracking the true count at the start of
+  every round.  Counts are truncated toward zero and clamped into
+  [min_true_count, max_true_count].  Returns (true count, frequency) pairs.
+  */
+pub fn true_count_frequencies(system: &CountingSystem, num_decks: u32, penetration: f64,
+                              min_true_count: i32, max_true_count: i32, rounds: u32,
+                              seed: u32) -> Vec<(i32, f64)> {
+    let mut shoe = CutCardShoe::new_seeded(cards_in_deck(num_decks), penetration, seed);
+    let mut tracker = CardShoeTracker::new(&mut shoe);
+    let mut seen: BTreeMap<i32, u32> = BTreeMap::new();
+    for _ in range(0, rounds) {
+        let mut tc = tracker.true_count(system).unwrap().trunc() as i32;
+        if tc < min_true_count {
+            tc = min_true_count;
+        } else if tc > max_true_count {
+            tc = max_true_count;
+        }
+        let current = match seen.get(&tc) {
+            Some(c) => *c,
+            None => 0,
+        };
+        seen.insert(tc, current + 1);
+        for _ in range(0, CARDS_PER_ROUND) {
+            tracker.pop();
+        }
+        tracker.end_round();
+    }
+    seen.iter().map(|(tc, c)| (*tc, *c as f64 / rounds as f64)).collect()
+}
+
+/**
+  The frequency of each true count, with the EV and variance of a shoe
+  depleted to that count halfway to the cut card.  Counts the depletion
+  can't reach are left out.  Every count solves a whole round's outcome
+  distribution, so keep the range of counts to the ones the ramp needs.
+  */
+pub fn count_stats(rules: &BJRules, system: &CountingSystem, num_decks: u32, penetration: f64,
+                   min_true_count: i32, max_true_count: i32, rounds: u32,
+                   seed: u32) -> Vec<CountStats> {
+    let cards_dealt = (num_decks as f64 * 52.0 * penetration / 2.0) as u32;
+    let mut ret = Vec::new();
+    for &(tc, frequency) in true_count_frequencies(system, num_decks, penetration, min_true_count,
+                                                   max_true_count, rounds, seed).iter() {
+        let composition = match deplete_to_true_count(system, num_decks, cards_dealt, tc as f64) {
+            Some(c) => c,
+            None => continue,
+        };
+        let mut cards = Vec::new();
+        let mut shoe = ShoeComposition::new_from_counts(composition).direct_shoe_seeded(&mut cards, seed);
+        let mut calc = ActionCalculator::new(*rules, &mut shoe);
+        let d = calc.total_outcome_distribution();
+        ret.push(CountStats {
+            true_count: tc,
+            frequency: frequency,
+            ev: d.mean(),
+            variance: d.variance(),
+        });
+    }
+    ret
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum RampObjective {
+    Score,
+    WinRate,
+}
+
+/**
+  Limits on a bet ramp.  Bets are in units of the minimum bet, and so is the
+  bankroll.
+  */
+#[derive(Copy, Clone)]
+pub struct RampConstraints {
+    pub bankroll: f64,
+    pub max_risk_of_ruin: f64,
+    pub max_spread: f64,
+    // Bets are multiples of this
+    pub bet_step: f64,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum RampError {
+    NoCounts,
+    // Bets have to go up in positive steps
+    BadBetStep(f64),
+    // The largest bet can't be under the one unit minimum
+    BadMaxSpread(f64),
+    // Every ramp's risk of ruin is over the limit
+    TooRisky,
+}
+
+impl fmt::Show for RampError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RampError::NoCounts => write!(f, "no count stats to bet on"),
+            RampError::BadBetStep(s) => write!(f, "bet step {} isn't positive", s),
+            RampError::BadMaxSpread(s) => write!(f, "max spread {} is under 1", s),
+            RampError::TooRisky => write!(f, "no ramp keeps the risk of ruin under the limit"),
+        }
+    }
+}
+
+pub struct BetRamp {
+    pub true_counts: Vec<i32>,
+    pub spread: BetSpread,
+}
+
+impl BetRamp {
+    pub fn bet_at(&self, true_count: i32) -> f64 {
+        let mut ret = self.spread.levels()[0].bet();
+        for (i, tc) in self.true_counts.iter().enumerate() {
+            if *tc <= true_count {
+                ret = self.spread.levels()[i].bet();
+            }
+        }
+        ret
+    }
+}
+
+fn build_spread(stats: &Vec<CountStats>, bets: &Vec<f64>) -> BetSpread {
+    BetSpread::new(stats.iter().zip(bets.iter()).map(
+        |(s, b)| BetLevel::new(s.frequency, *b, s.ev, s.variance)).collect())
+}
+
+// Feasible ramps always beat infeasible ones, which are ranked by win rate
+fn evaluate(spread: &BetSpread, objective: RampObjective,
+            constraints: &RampConstraints) -> (bool, f64) {
+    if spread.risk_of_ruin(constraints.bankroll) > constraints.max_risk_of_ruin {
+        return (false, spread.win_rate());
+    }
+    match objective {
+        RampObjective::Score => (true, spread.score()),
+        RampObjective::WinRate => (true, spread.win_rate()),
+    }
+}
+
+fn better(a: (bool, f64), b: (bool, f64)) -> bool {
+    (a.0 && !b.0) || (a.0 == b.0 && a.1 > b.1 + 1e-12)
+}
+
+/**
+  Searches for the bet at each count that best meets the objective.  Bets
+  never go down as the count goes up, and stay between one unit and
+  max_spread units.
+  */
+pub fn optimize_ramp(stats: &Vec<CountStats>, objective: RampObjective,
+                     constraints: &RampConstraints) -> Result<BetRamp, RampError> {
+    if stats.len() == 0 {
+        return Err(RampError::NoCounts);
+    }
+    if !(constraints.bet_step > 0.0) {
+        return Err(RampError::BadBetStep(constraints.bet_step));
+    }
+    if !(constraints.max_spread >= 1.0) {
+        return Err(RampError::BadMaxSpread(constraints.max_spread));
+    }
+    let mut order: Vec<usize> = range(0, stats.len()).collect();
+    order.sort_by(|a, b| stats[*a].true_count.cmp(&stats[*b].true_count));
+    let sorted: Vec<CountStats> = order.iter().map(|i| CountStats {
+        true_count: stats[*i].true_count,
+        frequency: stats[*i].frequency,
+        ev: stats[*i].ev,
+        variance: stats[*i].variance,
+    }).collect();
+
+    let mut candidates = Vec::new();
+    let mut b = 1.0;
+    while b <= constraints.max_spread + 1e-9 {
+        candidates.push(b);
+        b += constraints.bet_step;
+    }
+
+    let mut bets = vec![1.0; sorted.len()];
+    let mut best = evaluate(&build_spread(&sorted, &bets), objective, constraints);
+    // Coordinate ascent: move one count's bet at a time until nothing helps
+    loop {
+        let mut improved = false;
+        for i in range(0, bets.len()) {
+            let low = if i == 0 { 1.0 } else { bets[i - 1] };
+            let high = if i + 1 == bets.len() { constraints.max_spread } else { bets[i + 1] };
+            for &c in candidates.iter().filter(|c| **c >= low && **c <= high) {
+                let old = bets[i];
+                bets[i] = c;
+                let e = evaluate(&build_spread(&sorted, &bets), objective, constraints);
+                if better(e, best) {
+                    best = e;
+                    improved = true;
+                } else {
+                    bets[i] = old;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+    if !best.0 {
+        return Err(RampError::TooRisky);
+    }
+    Ok(BetRamp {
+        true_counts: sorted.iter().map(|s| s.true_count).collect(),
+        spread: build_spread(&sorted, &bets),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use betramp::{optimize_ramp, true_count_frequencies, count_stats, CountStats, RampConstraints};
+    use betramp::RampError;
+    use rules::BJRules;
+    use betramp::RampObjective::{Score, WinRate};
+    use counting::HI_LO;
+    use std::num::Float;
+
+    fn stats() -> Vec<CountStats> {
+        vec![
+            CountStats { true_count: 3, frequency: 0.15, ev: 0.02, variance: 1.35 },
+            CountStats { true_count: -1, frequency: 0.6, ev: -0.01, variance: 1.3 },
+            CountStats { true_count: 1, frequency: 0.25, ev: 0.004, variance: 1.32 },
+        ]
+    }
+
+    #[test]
+    fn test_optimize_ramp() {
+        let constraints = RampConstraints {
+            bankroll: 2000.0,
+            max_risk_of_ruin: 0.05,
+            max_spread: 8.0,
+            bet_step: 1.0,
+        };
+        let ramp = optimize_ramp(&stats(), WinRate, &constraints).unwrap();
+        assert!(ramp.true_counts == vec![-1, 1, 3]);
+        assert_eq!(1.0, ramp.bet_at(-1));
+        assert_eq!(1.0, ramp.bet_at(-5));
+        assert_eq!(8.0, ramp.bet_at(3));
+        assert!(ramp.spread.spread() <= 8.0);
+        assert!(ramp.spread.risk_of_ruin(2000.0) <= 0.05);
+
+        let scored = optimize_ramp(&stats(), Score, &constraints).unwrap();
+        assert!(scored.spread.score() >= ramp.spread.score());
+
+        // Too little money to play at all
+        let broke = RampConstraints { bankroll: 5.0, .. constraints };
+        assert!(optimize_ramp(&stats(), WinRate, &broke).err() == Some(RampError::TooRisky));
+    }
+
+    #[test]
+    fn test_bad_constraints() {
+        let constraints = RampConstraints {
+            bankroll: 2000.0,
+            max_risk_of_ruin: 0.05,
+            max_spread: 8.0,
+            bet_step: 0.0,
+        };
+        assert!(optimize_ramp(&stats(), WinRate, &constraints).err() ==
+                Some(RampError::BadBetStep(0.0)));
+        let narrow = RampConstraints { max_spread: 0.5, bet_step: 1.0, .. constraints };
+        assert!(optimize_ramp(&stats(), WinRate, &narrow).err() ==
+                Some(RampError::BadMaxSpread(0.5)));
+        assert!(optimize_ramp(&Vec::new(), WinRate, &narrow).err() == Some(RampError::NoCounts));
+    }
+
+    #[test]
+    fn test_count_stats() {
+        // Every count is clamped to 0, so a single depleted deck gets solved
+        let s = count_stats(&BJRules::new(), &HI_LO, 1, 0.5, 0, 0, 200, 3);
+        assert_eq!(1, s.len());
+        assert_eq!(0, s[0].true_count);
+        assert_eq!(1.0, s[0].frequency);
+        assert!(s[0].ev.abs() < 0.05);
+        assert!(s[0].variance > 1.0);
+    }
+
+    #[test]
+    fn test_true_count_frequencies() {
+        let f = true_count_frequencies(&HI_LO, 6, 0.75, -5, 5, 2000, 3);
+        let total = f.iter().fold(0.0, |sum, x| sum + x.1);
+        assert!((total - 1.0).abs() < 0.000001);
+        let zero = f.iter().find(|x| x.0 == 0).unwrap().1;
+        assert!(f.iter().all(|x| x.1 <= zero));
+    }
+}
@@ -0,0 +1,34 @@
+extern crate gambling;
+
+use std::io::File;
+use std::os;
+use gambling::audit::audit;
+use gambling::history::HandHistory;
+
+/**
+  Prints every mistake in a hand history file and the EV lost in each kind
+  of decision.
+  */
+fn main() {
+    let args = os::args();
+    if args.len() != 2 {
+        println!("Usage: bjaudit <hand history file>");
+        os::set_exit_status(1);
+        return;
+    }
+    let text = match File::open(&Path::new(args[1].as_slice())).read_to_string() {
+        Ok(t) => t,
+        Err(e) => {
+            println!("Couldn't read {}: {}", args[1], e);
+            os::set_exit_status(1);
+            return;
+        }
+    };
+    match HandHistory::parse(text.as_slice()).and_then(|h| audit(&h)) {
+        Ok(report) => print!("{:?}", report),
+        Err(e) => {
+            println!("{:?}", e);
+            os::set_exit_status(1);
+        }
+    }
+}
@@ -0,0 +1,559 @@
+extern crate gambling;
+
+use std::os;
+use gambling::action_calculator::{ActionCalculator, StrategyMode};
+use gambling::cards::value::Value;
+use gambling::cards::parse::parse_values;
+use gambling::hand::BJHand;
+use gambling::hand::SCORE_VALUES;
+use gambling::hole_card::HoleCardHint;
+use gambling::payoff::{Payoff, SessionPlan};
+use gambling::report::{Table, Cell, Format};
+use gambling::rules::BJRules;
+use gambling::rules::RuleSet;
+use gambling::shoe::shoe::DirectShoe;
+use gambling::shoe::composition::ShoeComposition;
+use gambling::shoe::randomshoe::new_infinite_shoe;
+use gambling::strategy_export::{starting_hands, best_with_ev};
+
+const USAGE: &'static str = "\
+Usage: bjcalc <actions|edge|modes|raise|tournament|payoff|session|spots|dealer|chart> [options]
+  actions           EV of every action for --hand against --up
+  edge              The house edge of the whole game
+  modes             The player's EV playing by totals, by two card hands and
+                    by the exact cards, and what each gains over totals.  The
+                    table of plays by total is one improvement on playing the
+                    exact cards, not the best one, so those EVs are marked
+                    approx and may be a little low
+  raise             Double Attack: the EV of each up card with and without
+                    raising, and whether to raise
+  tournament        Odds of ending the round with --target chips from --chips
+                    for each of --bet, or of each action for --hand against
+                    --up, playing for the target.  Doubles and splits can
+                    only stake the chips left after the bet
+  payoff            The expected --payoff of each action for --hand against
+                    --up, or of the whole round, playing for it
+  session           When to quit a session of --rounds rounds paid through
+                    --payoff, like a loss rebate on the session's net
+  spots             Playing 1 to --hands hands a round: the EV and variance
+                    of the round, SCORE, the Kelly bet per hand and what a
+                    round is worth with --bankroll.  The hands share the
+                    dealer's hand but not each other's cards, as with an
+                    infinite deck
+  dealer            Odds of each final dealer total, for --up or every up card.
+                    The totals leave out the blackjacks found by peeking,
+                    which are in the blackjack column; without a peek a
+                    blackjack is counted as 21
+  chart             The best action for every starting hand and up card
+Options:
+  --hand CARDS      Player cards, like T6 or A7
+  --up CARD         Dealer up card, like 9 or A
+  --rules R         Rules, like "6D H17 DAS LS SPL3 BJ3:2".  Decks default to 6,
+                    Spanish ones with DA
+  --decks N         A shoe of N decks, Spanish ones with DA, whatever --rules
+                    says
+  --composition C   6D, 6S for Spanish decks, or ten counts ace first and tens
+                    last: 4,4,4,4,4,4,4,4,4,16
+  --infinite        An infinite shoe
+  --hole H          What the dealer's hole card is known to be: T, 2-8 or A,T
+  --chips N         Chips before the round
+  --target N        Chips to end the round with
+  --bet B           Bets to try, like 25 or 25,50,100 (default all the chips)
+  --payoff P        units, at_least:N, rebate:R, promo:P or match:P, where P
+                    is what keeping a chip or coupon on a push is worth
+  --rounds N        Rounds in a session (default 100)
+  --hands N         Most hands to play at once (default 3)
+  --bankroll B      Bankroll in units of the bet per hand (default 1000)
+  --format F        text, csv or json (default text)";
+
+enum ShoeDescription {
+    Finite(ShoeComposition),
+    Infinite,
+}
+
+struct Options {
+    command: String,
+    hand: Option<Vec<Value>>,
+    up: Option<Value>,
+    shoe: ShoeDescription,
+    format: Format,
+    rules: BJRules,
+    hole: HoleCardHint,
+    chips: Option<f64>,
+    target: Option<f64>,
+    bets: Vec<f64>,
+    payoff: Payoff,
+    rounds: u32,
+    hands: u32,
+    bankroll: f64,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    if args.len() == 0 {
+        return Err("Missing a command".to_string());
+    }
+    let mut hand = None;
+    let mut up = None;
+    // The last of --decks, --composition and --infinite, which beat the rules
+    let mut decks = None;
+    let mut shoe = None;
+    let mut rule_decks = None;
+    let mut format = Format::Text;
+    let mut rules = BJRules::new();
+    let mut hole = HoleCardHint::new_unknown();
+    let mut chips = None;
+    let mut target = None;
+    let mut bets = Vec::new();
+    let mut payoff = Payoff::Units;
+    let mut rounds = 100;
+    let mut hands = 3;
+    let mut bankroll = 1000.0;
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_slice();
+        let takes_value = match flag {
+            "--hand" | "--up" | "--decks" | "--composition" | "--format" | "--rules" | "--hole" |
+                "--chips" | "--target" | "--bet" | "--payoff" | "--rounds" |
+                "--hands" | "--bankroll" => true,
+            _ => false,
+        };
+        let value = if takes_value {
+            i += 1;
+            match args.get(i) {
+                Some(v) => v.as_slice(),
+                None => return Err(format!("{} needs a value", flag)),
+            }
+        } else {
+            ""
+        };
+        match flag {
+            "--hand" => hand = Some(try!(parse_values(value).map_err(|e| format!("{:?}", e)))),
+            "--up" => up = Some(try!(value.parse::<Value>().map_err(|e| format!("{:?}", e)))),
+            "--decks" => {
+                decks = Some(try!(value.parse::<u32>().ok().ok_or(
+                    format!("Bad deck count {}", value))));
+                shoe = None;
+            }
+            "--composition" => {
+                shoe = Some(ShoeDescription::Finite(try!(
+                    value.parse::<ShoeComposition>().map_err(|e| format!("{:?}", e)))));
+                decks = None;
+            }
+            "--infinite" => {
+                shoe = Some(ShoeDescription::Infinite);
+                decks = None;
+            }
+            "--hole" => hole = try!(value.parse::<HoleCardHint>().map_err(|e| format!("{:?}", e))),
+            "--chips" => chips = Some(try!(value.parse::<f64>().ok().ok_or(
+                format!("Bad chip count {}", value)))),
+            "--target" => target = Some(try!(value.parse::<f64>().ok().ok_or(
+                format!("Bad target {}", value)))),
+            "--payoff" => payoff = try!(value.parse::<Payoff>()),
+            "--rounds" => rounds = try!(value.parse::<u32>().ok().ok_or(
+                format!("Bad round count {}", value))),
+            "--hands" => hands = try!(value.parse::<u32>().ok().and_then(
+                |n| if n > 0 { Some(n) } else { None }).ok_or(format!("Bad hand count {}", value))),
+            "--bankroll" => bankroll = try!(value.parse::<f64>().ok().ok_or(
+                format!("Bad bankroll {}", value))),
+            "--bet" => for b in value.split(',') {
+                bets.push(try!(b.trim().parse::<f64>().ok().ok_or(format!("Bad bet {}", b))));
+            },
+            "--format" => format = try!(value.parse::<Format>()),
+            "--rules" => {
+                let set = try!(RuleSet::parse(value).map_err(|e| format!("{:?}", e)));
+                rules = set.rules;
+                rule_decks = set.decks;
+            }
+            _ => return Err(format!("Unknown option {}", flag)),
+        }
+        i += 1;
+    }
+    let shoe = match shoe {
+        Some(ShoeDescription::Infinite) if rules.double_attack() => {
+            return Err("--infinite can't be used with DA, which deals Spanish decks".to_string());
+        }
+        Some(s) => s,
+        None => {
+            let n = decks.or(rule_decks).unwrap_or(6);
+            ShoeDescription::Finite(if rules.double_attack() {
+                ShoeComposition::new_spanish_decks(n)
+            } else {
+                ShoeComposition::new_decks(n)
+            })
+        }
+    };
+    Ok(Options {
+        command: args[0].clone(),
+        hand: hand,
+        up: up,
+        shoe: shoe,
+        format: format,
+        rules: rules,
+        hole: hole,
+        chips: chips,
+        target: target,
+        bets: bets,
+        payoff: payoff,
+        rounds: rounds,
+        hands: hands,
+        bankroll: bankroll,
+    })
+}
+
+fn run(options: &Options, shoe: &mut DirectShoe) -> Result<Table, String> {
+    let mut calc = ActionCalculator::new(options.rules, shoe);
+    calc.set_hole_card_hint(options.hole);
+    match options.command.as_slice() {
+        "actions" => {
+            let (hand, up) = match (&options.hand, options.up) {
+                (&Some(ref h), Some(u)) => (h.clone(), u),
+                _ => return Err("actions needs --hand and --up".to_string()),
+            };
+            let evs = try!(calc.action_values(&hand, &up).ok_or(
+                "The shoe doesn't hold those cards".to_string()));
+            let mut t = Table::new(&["action", "ev"]);
+            for &(a, ev) in evs.iter() {
+                t.add_row(vec![Cell::Text(format!("{:?}", a)), Cell::Number(ev)]);
+            }
+            Ok(t)
+        }
+        "edge" => {
+            let ev = calc.total_expected_best_value();
+            let mut t = Table::new(&["player_ev", "house_edge"]);
+            t.add_row(vec![Cell::Number(ev), Cell::Number(-ev)]);
+            Ok(t)
+        }
+        "modes" => {
+            let values = calc.strategy_mode_values();
+            let mut t = Table::new(&["strategy", "player_ev", "gain"]);
+            for &(mode, ev) in values.iter() {
+                let name = match mode {
+                    StrategyMode::CompositionDependent => format!("{:?}", mode),
+                    _ => format!("{:?} (approx)", mode),
+                };
+                t.add_row(vec![Cell::Text(name), Cell::Number(ev),
+                               Cell::Number(ev - values[0].1)]);
+            }
+            Ok(t)
+        }
+        "raise" => {
+            if !options.rules.double_attack() {
+                return Err("raise needs Double Attack rules, like --rules \"8D DA\"".to_string());
+            }
+            let mut t = Table::new(&["up", "odds", "ev", "raised_ev", "raise"]);
+            for &(up, odds, ev, raised_ev) in calc.double_attack_values().iter() {
+                t.add_row(vec![Cell::Text(up.char().to_string()), Cell::Number(odds),
+                                 Cell::Number(ev), Cell::Number(raised_ev),
+                                 Cell::Text((if raised_ev > ev { "yes" } else { "no" }).to_string())]);
+            }
+            Ok(t)
+        }
+        "tournament" => {
+            let (chips, target) = match (options.chips, options.target) {
+                (Some(c), Some(t)) => (c, t),
+                _ => return Err("tournament needs --chips and --target".to_string()),
+            };
+            let bets = if options.bets.len() > 0 { options.bets.clone() } else { vec![chips] };
+            match (&options.hand, options.up) {
+                (&Some(ref hand), Some(up)) => {
+                    let odds = try!(calc.tournament_action_values(hand, &up, chips, bets[0], target)
+                                    .ok_or("The shoe doesn't hold those cards".to_string()));
+                    let mut t = Table::new(&["action", "p_target"]);
+                    for &(a, p) in odds.iter() {
+                        t.add_row(vec![Cell::Text(format!("{:?}", a)), Cell::Number(p)]);
+                    }
+                    Ok(t)
+                }
+                _ => {
+                    let best = calc.tournament_best_bet(chips, target, bets.as_slice());
+                    let mut t = Table::new(&["bet", "p_target", "best"]);
+                    for bet in bets.iter() {
+                        let is_best = best.map_or(false, |b| b.0 == *bet);
+                        t.add_row(vec![Cell::Number(*bet),
+                                         Cell::Number(calc.tournament_odds(chips, *bet, target)),
+                                         Cell::Text((if is_best { "yes" } else { "no" }).to_string())]);
+                    }
+                    Ok(t)
+                }
+            }
+        }
+        "payoff" => {
+            match (&options.hand, options.up) {
+                (&Some(ref hand), Some(up)) => {
+                    let values = try!(calc.payoff_action_values(hand, &up, options.payoff)
+                                      .ok_or("The shoe doesn't hold those cards".to_string()));
+                    let mut t = Table::new(&["action", "payoff"]);
+                    for &(a, v) in values.iter() {
+                        t.add_row(vec![Cell::Text(format!("{:?}", a)), Cell::Number(v)]);
+                    }
+                    Ok(t)
+                }
+                _ => {
+                    let d = calc.payoff_distribution(options.payoff);
+                    let mut t = Table::new(&["player_ev", "payoff"]);
+                    t.add_row(vec![Cell::Number(d.mean()),
+                                     Cell::Number(options.payoff.expected(&d))]);
+                    Ok(t)
+                }
+            }
+        }
+        "session" => {
+            // Rounds are played for units; the payoff is on the session
+            let plan = SessionPlan::new(&calc.total_outcome_distribution(), options.payoff,
+                                        options.rounds);
+            // What the whole session is worth goes on the first row
+            let mut t = Table::new(&["rounds_played", "quit_at", "session_value"]);
+            for n in range(0, options.rounds + 1) {
+                let ranges: Vec<String> = plan.quit_ranges(n).iter().map(|&(low, high)| {
+                    if low == high { format!("{}", low) } else { format!("{}..{}", low, high) }
+                }).collect();
+                let value = if n == 0 { Cell::Number(plan.value()) } else { Cell::Text(String::new()) };
+                t.add_row(vec![Cell::Text(n.to_string()), Cell::Text(ranges.connect(" ")), value]);
+            }
+            Ok(t)
+        }
+        "spots" => {
+            let one = calc.multiple_hands_infinite_deck(1);
+            let best = one.best_hands(options.hands, options.bankroll).hands();
+            let mut t = Table::new(&["hands", "ev", "variance", "correlation", "score",
+                                     "kelly_bet", "certainty_equivalent", "best"]);
+            for n in range(1, options.hands + 1) {
+                let m = one.with_hands(n);
+                t.add_row(vec![Cell::Text(n.to_string()), Cell::Number(m.ev()),
+                                 Cell::Number(m.variance()), Cell::Number(m.correlation()),
+                                 Cell::Number(m.score()),
+                                 Cell::Number(m.kelly_bet(options.bankroll, 1.0)),
+                                 Cell::Number(m.certainty_equivalent(options.bankroll)),
+                                 Cell::Text((if n == best { "yes" } else { "no" }).to_string())]);
+            }
+            Ok(t)
+        }
+        "dealer" => {
+            let ups: Vec<Value> = match options.up {
+                Some(u) => vec![u],
+                None => SCORE_VALUES.iter().map(|v| *v).collect(),
+            };
+            let mut t = Table::new(&["up", "blackjack", "17", "18", "19", "20", "21", "bust"]);
+            for up in ups.iter() {
+                let mut dealer_hand = match BJHand::new_from_deck(calc.shoe(), &vec![*up]) {
+                    Some(h) => h,
+                    None => continue,
+                };
+                // dealer_outcomes is once the dealer has checked for blackjack
+                let blackjack = if options.rules.dealer_blackjack_after_hand() {
+                    0.0
+                } else {
+                    calc.odds_of_dealer_blackjack(&dealer_hand.cards()[0])
+                };
+                let outcomes = calc.dealer_outcomes(&mut dealer_hand);
+                calc.shoe().insert(&dealer_hand.cards()[0]);
+                let mut row = vec![Cell::Text(up.char().to_string()), Cell::Number(blackjack)];
+                row.extend(outcomes.iter().map(|o| Cell::Number(*o * (1.0 - blackjack))));
+                t.add_row(row);
+            }
+            Ok(t)
+        }
+        "chart" => {
+            let mut header = vec!["hand"];
+            let ups: Vec<String> = SCORE_VALUES.iter().map(|v| v.char().to_string()).collect();
+            header.extend(ups.iter().map(|u| u.as_slice()));
+            let mut t = Table::new(header.as_slice());
+            for hand in starting_hands().iter() {
+                let mut row = vec![Cell::Text(hand.iter().map(|v| v.char()).collect())];
+                for up in SCORE_VALUES.iter() {
+                    let best = calc.action_values(hand, up).and_then(|evs| best_with_ev(&evs));
+                    row.push(Cell::Text(match best {
+                        Some((a, _)) => format!("{:?}", a),
+                        None => "-".to_string(),
+                    }));
+                }
+                t.add_row(row);
+            }
+            Ok(t)
+        }
+        other => Err(format!("Unknown command {}", other)),
+    }
+}
+
+/**
+  Answers rule and shoe questions from the command line, so nobody has to
+  edit and rebuild a test to get them.
+  */
+fn main() {
+    let args = os::args();
+    let options = match parse_options(&args[1..]) {
+        Ok(o) => o,
+        Err(e) => {
+            println!("{}", e);
+            println!("{}", USAGE);
+            os::set_exit_status(1);
+            return;
+        }
+    };
+    let result = match options.shoe {
+        ShoeDescription::Infinite => run(&options, &mut new_infinite_shoe()),
+        ShoeDescription::Finite(c) => {
+            let mut cards = Vec::new();
+            run(&options, &mut c.direct_shoe(&mut cards))
+        }
+    };
+    match result {
+        Ok(t) => print!("{}", t.render(&options.format)),
+        Err(e) => {
+            println!("{}", e);
+            os::set_exit_status(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_options, run, ShoeDescription};
+    use gambling::shoe::composition::ShoeComposition;
+    use gambling::report::{Table, Cell, Format};
+    use gambling::shoe::randomshoe::new_infinite_shoe;
+
+    fn args(a: &[&str]) -> Vec<String> {
+        a.iter().map(|s| s.to_string()).collect()
+    }
+
+    // Runs against an infinite shoe whatever the options say
+    fn run_args(a: &[&str]) -> Result<Table, String> {
+        let options = try!(parse_options(args(a).as_slice()));
+        run(&options, &mut new_infinite_shoe())
+    }
+
+    fn number(c: &Cell) -> f64 {
+        match *c {
+            Cell::Number(n) => n,
+            Cell::Text(ref s) => panic!("{} isn't a number", s),
+        }
+    }
+
+    fn column(t: &Table, name: &str) -> Vec<Cell> {
+        let i = t.header().iter().position(|h| h.as_slice() == name).unwrap();
+        t.rows().iter().map(|r| r[i].clone()).collect()
+    }
+
+    #[test]
+    fn test_bad_options() {
+        assert!(parse_options(&[]).is_err());
+        assert!(parse_options(args(&["edge", "--nope"]).as_slice()).is_err());
+        assert!(parse_options(args(&["actions", "--hand"]).as_slice()).is_err());
+        assert!(parse_options(args(&["actions", "--hand", ""]).as_slice()).is_err());
+        assert!(parse_options(args(&["edge", "--format", "xml"]).as_slice()).is_err());
+        assert!(parse_options(args(&["spots", "--hands", "0"]).as_slice()).is_err());
+        assert!(parse_options(args(&["tournament", "--bet", "25,x"]).as_slice()).is_err());
+        assert!(parse_options(args(&["payoff", "--payoff", "rebate:2"]).as_slice()).is_err());
+        assert!(run_args(&["nope"]).is_err());
+        assert!(run_args(&["actions", "--hand", "T6"]).is_err());
+    }
+
+    #[test]
+    fn test_options() {
+        let o = parse_options(args(&["edge", "--rules", "2D H17", "--format", "csv"]).as_slice()).unwrap();
+        assert!(o.format == Format::Csv);
+        assert!(o.rules.dealer_hits_soft_score(17));
+        match o.shoe {
+            ShoeDescription::Finite(c) => assert_eq!(104, c.len()),
+            ShoeDescription::Infinite => panic!("Expected two decks"),
+        }
+        let o = parse_options(args(&["edge", "--rules", "2D", "--infinite"]).as_slice()).unwrap();
+        assert!(match o.shoe { ShoeDescription::Infinite => true, _ => false });
+    }
+
+    // The shoe the options describe, None for an infinite one
+    fn shoe_of(a: &[&str]) -> Option<ShoeComposition> {
+        match parse_options(args(a).as_slice()).unwrap().shoe {
+            ShoeDescription::Finite(c) => Some(c),
+            ShoeDescription::Infinite => None,
+        }
+    }
+
+    #[test]
+    fn test_shoe_options() {
+        let spanish = Some(ShoeComposition::new_spanish_decks(6));
+        assert!(shoe_of(&["edge"]) == Some(ShoeComposition::new_decks(6)));
+        // The rules' decks are the fallback, whichever order the flags come in
+        assert!(shoe_of(&["edge", "--rules", "2D", "--decks", "4"]) ==
+                Some(ShoeComposition::new_decks(4)));
+        assert!(shoe_of(&["edge", "--decks", "4", "--rules", "2D"]) ==
+                Some(ShoeComposition::new_decks(4)));
+        assert!(shoe_of(&["edge", "--infinite", "--rules", "2D"]).is_none());
+        // Double Attack deals Spanish decks unless given a composition
+        assert!(shoe_of(&["raise", "--rules", "DA"]) == spanish);
+        assert!(shoe_of(&["raise", "--rules", "DA", "--decks", "8"]) ==
+                Some(ShoeComposition::new_spanish_decks(8)));
+        assert!(shoe_of(&["raise", "--decks", "8", "--rules", "2D DA"]) ==
+                Some(ShoeComposition::new_spanish_decks(8)));
+        assert!(shoe_of(&["raise", "--composition", "8D", "--rules", "DA"]) ==
+                Some(ShoeComposition::new_decks(8)));
+        assert!(shoe_of(&["raise", "--rules", "DA", "--composition", "8D"]) ==
+                Some(ShoeComposition::new_decks(8)));
+        assert!(parse_options(args(&["raise", "--infinite", "--rules", "DA"]).as_slice()).is_err());
+        assert!(parse_options(args(&["raise", "--rules", "DA", "--infinite"]).as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_actions_and_edge() {
+        let t = run_args(&["actions", "--hand", "T6", "--up", "T"]).unwrap();
+        assert!(column(&t, "action").iter().any(|a| *a == Cell::Text("STAND".to_string())));
+        let t = run_args(&["edge"]).unwrap();
+        assert_eq!(1, t.rows().len());
+        let ev = number(&t.rows()[0][0]);
+        assert_eq!(-ev, number(&t.rows()[0][1]));
+    }
+
+    #[test]
+    fn test_modes() {
+        let t = run_args(&["modes"]).unwrap();
+        assert_eq!(3, t.rows().len());
+        assert_eq!(0.0, number(&t.rows()[0][2]));
+        assert_eq!(Cell::Text("TotalDependent (approx)".to_string()), t.rows()[0][0]);
+        assert_eq!(Cell::Text("CompositionDependent".to_string()), t.rows()[2][0]);
+    }
+
+    #[test]
+    fn test_raise() {
+        assert!(run_args(&["raise"]).is_err());
+        let t = run_args(&["raise", "--rules", "DA"]).unwrap();
+        assert_eq!(10, t.rows().len());
+    }
+
+    #[test]
+    fn test_tournament() {
+        assert!(run_args(&["tournament", "--chips", "100"]).is_err());
+        let t = run_args(&["tournament", "--chips", "100", "--target", "200",
+                           "--bet", "50,100"]).unwrap();
+        assert_eq!(2, t.rows().len());
+        let best = column(&t, "best");
+        assert_eq!(1, best.iter().filter(|b| **b == Cell::Text("yes".to_string())).count());
+    }
+
+    #[test]
+    fn test_payoff_and_session() {
+        let t = run_args(&["payoff", "--payoff", "rebate:0.1"]).unwrap();
+        assert!(number(&t.rows()[0][1]) >= number(&t.rows()[0][0]));
+        let t = run_args(&["session", "--payoff", "rebate:0.1", "--rounds", "3"]).unwrap();
+        assert_eq!(4, t.rows().len());
+        assert!(match t.rows()[1][2] { Cell::Text(ref s) => s.len() == 0, _ => false });
+    }
+
+    #[test]
+    fn test_spots() {
+        let t = run_args(&["spots", "--hands", "2", "--bankroll", "100"]).unwrap();
+        assert_eq!(2, t.rows().len());
+        let best = column(&t, "best");
+        assert_eq!(1, best.iter().filter(|b| **b == Cell::Text("yes".to_string())).count());
+    }
+
+    #[test]
+    fn test_dealer() {
+        let t = run_args(&["dealer", "--up", "A"]).unwrap();
+        assert_eq!(1, t.rows().len());
+        let total = t.rows()[0][1..].iter().fold(0.0, |sum, c| sum + number(c));
+        assert!((total - 1.0).abs() < 0.000001);
+        assert!(number(&t.rows()[0][1]) > 0.3);
+        assert_eq!(10, run_args(&["dealer"]).unwrap().rows().len());
+    }
+}
@@ -0,0 +1,166 @@
+extern crate gambling;
+
+use std::io;
+use std::os;
+use gambling::action_calculator::ActionCalculator;
+use gambling::bjaction::BJAction;
+use gambling::bjaction::BJAction::{HIT, STAND, DOUBLE, SPLIT, SURRENDER};
+use gambling::hole_card::HoleCardHint;
+use gambling::round::{Round, RoundState};
+use gambling::rules::RuleSet;
+use gambling::shoe::shoe::DirectShoe;
+use gambling::strategy_export::best_with_ev;
+
+/**
+  Deals rounds in the terminal.  After every decision it shows the best play
+  for the exact cards left in the shoe and what the choice cost.
+
+  The only argument is the rule set, like "6D H17 DAS LS SPL3 BJ3:2".
+  */
+fn main() {
+    let args = os::args();
+    let rule_set = match RuleSet::parse(args[1..].connect(" ").as_slice()) {
+        Ok(r) => r,
+        Err(e) => {
+            println!("{:?}", e);
+            println!("Usage: blackjack [rules, like 6D H17 DAS LS SPL3 BJ3:2]");
+            return;
+        }
+    };
+    let rules = rule_set.rules;
+    let num_decks = rule_set.decks.unwrap_or(6);
+    // Spanish decks for Double Attack
+    let composition = rule_set.composition(6);
+    let mut shoe = composition.generic_shoe();
+    let mut stdin = io::stdin();
+    let mut total = 0.0;
+    let mut total_cost = 0.0;
+    println!("Playing {} decks with {:?}.  Type q to quit.", num_decks, rules);
+    loop {
+        if shoe.len() < (composition.len() / 4) as usize {
+            println!("Shuffling");
+            shoe = composition.generic_shoe();
+        }
+        let mut round = match Round::deal(rules, &mut shoe, 1.0) {
+            Ok(r) => r,
+            Err(e) => {
+                println!("Couldn't deal: {:?}", e);
+                return;
+            }
+        };
+        println!("");
+        match round.dealer_hand() {
+            // Double Exposure
+            Some(h) => println!("Dealer shows {:?} ({})", h.cards(), h.score()),
+            None => println!("Dealer shows {:?}", round.dealer_up_card()),
+        }
+        if round.state() == RoundState::Insurance {
+            print_hands(&round);
+            let take = match prompt(&mut stdin, "Insurance? [y/n] ") {
+                Some(line) => line.as_slice().starts_with("y"),
+                None => return,
+            };
+            let insured_ev = round.insurance_ev(round.insurance_seat().unwrap());
+            let chosen_ev = if take { insured_ev } else { 0.0 };
+            let best_ev = if insured_ev > 0.0 { insured_ev } else { 0.0 };
+            if chosen_ev == best_ev {
+                println!("  Correct: insurance is worth {:.4}", insured_ev);
+            } else {
+                println!("  Insurance is worth {:.4}, so that cost {:.4}", insured_ev,
+                         best_ev - chosen_ev);
+                total_cost += best_ev - chosen_ev;
+            }
+            round.take_insurance(take).unwrap();
+        }
+        while round.state() == RoundState::PlayerTurn {
+            print_hands(&round);
+            let legal = round.legal_actions();
+            let choices: Vec<String> = legal.iter().map(|a| format!("{}={:?}", key(*a), a)).collect();
+            let line = match prompt(&mut stdin, format!("{} ", choices.connect(" ")).as_slice()) {
+                Some(line) => line,
+                None => return,
+            };
+            if line.as_slice().starts_with("q") {
+                return;
+            }
+            let action = match legal.iter().find(|a| line.as_slice().starts_with(key(**a))) {
+                Some(a) => *a,
+                None => {
+                    println!("Pick one of {}", choices.connect(" "));
+                    continue;
+                }
+            };
+            let evs = action_evs(&round);
+            match (best_with_ev(&evs), evs.iter().find(|x| x.0 == action)) {
+                (Some(b), Some(chosen)) => {
+                    if b.0 == action {
+                        println!("  Correct: {:?} is worth {:.4}", action, chosen.1);
+                    } else {
+                        println!("  Best was {:?} at {:.4}; {:?} is worth {:.4} and cost {:.4}",
+                                 b.0, b.1, action, chosen.1, b.1 - chosen.1);
+                        total_cost += b.1 - chosen.1;
+                    }
+                }
+                _ => {}
+            }
+            match round.act(action) {
+                Ok(()) => {}
+                Err(e) => println!("  {:?}", e),
+            }
+        }
+        print_hands(&round);
+        let net = round.net_result().unwrap();
+        total += net;
+        println!("Dealer has {:?} ({})", round.dealer_hand().unwrap().cards(),
+                 round.dealer_hand().unwrap().score());
+        println!("Round: {:+.1}  Total: {:+.1}  Cost of mistakes: {:.4}", net, total, total_cost);
+    }
+}
+
+fn key(a: BJAction) -> &'static str {
+    match a {
+        HIT => "h",
+        STAND => "s",
+        DOUBLE => "d",
+        SPLIT => "p",
+        SURRENDER => "r",
+    }
+}
+
+fn prompt(stdin: &mut io::stdio::StdinReader, question: &str) -> Option<String> {
+    print!("{}", question);
+    // The prompt has no newline to flush it
+    io::stdio::flush();
+    match stdin.read_line() {
+        Ok(line) => Some(line.as_slice().trim().to_string()),
+        Err(_) => None,
+    }
+}
+
+fn print_hands(round: &Round) {
+    for (i, h) in round.hands().iter().enumerate() {
+        let marker = if round.active_hand_index() == Some(i) { ">" } else { " " };
+        println!("{} {:?} ({}) bet {}", marker, h.hand().cards(), h.hand().score(), h.wager());
+    }
+}
+
+// EV of each legal action for the active hand, from the cards the player can't see
+fn action_evs(round: &Round) -> Vec<(BJAction, f64)> {
+    let mut cards = Vec::new();
+    let mut shoe = round.unseen_composition().direct_shoe(&mut cards);
+    let mut calc = ActionCalculator::new(*round.rules(), &mut shoe);
+    match round.dealer_hand() {
+        Some(h) => calc.set_hole_card_hint(HoleCardHint::new_exact(h.cards()[1].value())),
+        None => {}
+    }
+    let mut hand = round.active_hand().unwrap().hand().clone();
+    let up = *round.dealer_up_card();
+    let mut ret = Vec::new();
+    for a in round.legal_actions().iter() {
+        match calc.expected_value(&mut hand, &up, *a, true) {
+            Some(ev) => ret.push((*a, ev)),
+            None => {}
+        }
+    }
+    ret
+}
@@ -8,6 +8,9 @@ pub enum BJAction {
     SURRENDER,
 }
 
+pub const BJ_ACTIONS: [BJAction;5] = [BJAction::STAND, BJAction::HIT, BJAction::DOUBLE,
+    BJAction::SPLIT, BJAction::SURRENDER];
+
 impl fmt::Show for BJAction {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::str::FromStr;
+use cards::card::Card;
+use cards::suit::Suit;
+use cards::suit::SUITS;
+use cards::value::Value;
+use cards::value::VALUES;
+use cards::value::TEN;
+
+/**
+  Why some text couldn't be read as cards.  input is the part that was
+  rejected, which may be a piece of a longer string.
+  */
+#[derive(Clone, PartialEq)]
+pub struct CardParseError {
+    pub input: String,
+    pub reason: &'static str,
+}
+
+impl CardParseError {
+    pub fn new(input: &str, reason: &'static str) -> CardParseError {
+        CardParseError {
+            input: String::from_str(input),
+            reason: reason,
+        }
+    }
+}
+
+impl fmt::Show for CardParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "can't parse '{}': {}", self.input, self.reason)
+    }
+}
+
+fn value_for_char(c: char) -> Option<Value> {
+    VALUES.iter().find(|v| v.char() == c.to_uppercase()).map(|v| *v)
+}
+
+fn suit_for_char(c: char) -> Option<Suit> {
+    SUITS.iter().find(|s| s.char() == c.to_lowercase()).map(|s| *s)
+}
+
+// A, 2 ... 9, T, J, Q, K in either case.  10 works too
+impl FromStr for Value {
+    type Err = CardParseError;
+    fn from_str(s: &str) -> Result<Value, CardParseError> {
+        let t = s.trim();
+        if t == "10" {
+            return Ok(TEN);
+        }
+        let mut chars = t.chars();
+        match (chars.next().and_then(value_for_char), chars.next()) {
+            (Some(v), None) => Ok(v),
+            _ => Err(CardParseError::new(s, "expected a rank like A, 7 or T")),
+        }
+    }
+}
+
+// s, c, h or d
+impl FromStr for Suit {
+    type Err = CardParseError;
+    fn from_str(s: &str) -> Result<Suit, CardParseError> {
+        let mut chars = s.trim().chars();
+        match (chars.next().and_then(suit_for_char), chars.next()) {
+            (Some(suit), None) => Ok(suit),
+            _ => Err(CardParseError::new(s, "expected a suit: s, c, h or d")),
+        }
+    }
+}
+
+// The way cards print: As, Td, Kh
+impl FromStr for Card {
+    type Err = CardParseError;
+    fn from_str(s: &str) -> Result<Card, CardParseError> {
+        let t = s.trim();
+        if t.len() < 2 {
+            return Err(CardParseError::new(s, "expected a rank and a suit, like As"));
+        }
+        // The suit is the last character, which may not be a single byte
+        let split = t.char_indices().last().unwrap().0;
+        let value = try!(t[..split].parse::<Value>());
+        let suit = try!(t[split..].parse::<Suit>());
+        Ok(Card::new(value, suit))
+    }
+}
+
+/**
+  Cards written one after another, like A7, T-6, 8,8 or AsKd.  Ranks can be
+  split up by spaces, dashes or commas.  A rank without a suit is given one,
+  rotating through the suits so the cards stay distinct.
+  */
+pub fn parse_cards(s: &str) -> Result<Vec<Card>, CardParseError> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut ret = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() || c == '-' || c == ',' {
+            i += 1;
+            continue;
+        }
+        let value = if c == '1' && i + 1 < chars.len() && chars[i + 1] == '0' {
+            i += 1;
+            TEN
+        } else {
+            match value_for_char(c) {
+                Some(v) => v,
+                None => return Err(CardParseError::new(s, "expected ranks like A7 or T-6")),
+            }
+        };
+        i += 1;
+        let suit = match chars.get(i).and_then(|c| if c.is_lowercase() { suit_for_char(*c) } else { None }) {
+            Some(suit) => {
+                i += 1;
+                suit
+            }
+            None => SUITS[ret.len() % SUITS.len()],
+        };
+        ret.push(Card::new(value, suit));
+    }
+    Ok(ret)
+}
+
+// Just the ranks of parse_cards, of which there has to be at least one
+pub fn parse_values(s: &str) -> Result<Vec<Value>, CardParseError> {
+    let cards = try!(parse_cards(s));
+    if cards.len() == 0 {
+        return Err(CardParseError::new(s, "expected at least one rank"));
+    }
+    Ok(cards.iter().map(|c| *c.value()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use cards::parse::{parse_cards, parse_values};
+    use cards::card::Card;
+    use cards::suit::{Suit, SPADE, CLUB, DIAMOND, HEART};
+    use cards::value::Value;
+    use cards::value::{ACE, SEVEN, TEN, KING, SIX};
+
+    #[test]
+    fn test_parse_cards() {
+        assert_eq!(ACE, "A".parse::<Value>().unwrap());
+        assert_eq!(TEN, "10".parse::<Value>().unwrap());
+        assert_eq!(KING, "k".parse::<Value>().unwrap());
+        assert!("X".parse::<Value>().is_err());
+        assert_eq!(HEART, "h".parse::<Suit>().unwrap());
+        assert!(Card::new(ACE, SPADE) == "As".parse::<Card>().unwrap());
+        assert!(Card::new(TEN, DIAMOND) == "10d".parse::<Card>().unwrap());
+        assert!("A♠".parse::<Card>().is_err());
+        assert!("♠♠".parse::<Card>().is_err());
+        let kh = Card::new(KING, HEART);
+        assert!(kh == format!("{:?}", kh).parse::<Card>().unwrap());
+        let e = "Ax".parse::<Card>().err().unwrap();
+        assert_eq!("can't parse 'x': expected a suit: s, c, h or d", format!("{:?}", e));
+    }
+
+    #[test]
+    fn test_parse_hands() {
+        assert!(parse_values("A7").unwrap() == vec![ACE, SEVEN]);
+        assert!(parse_values("T-6").unwrap() == vec![TEN, SIX]);
+        assert!(parse_values("10, 6").unwrap() == vec![TEN, SIX]);
+        let cards = parse_cards("AsKc7").unwrap();
+        assert!(cards[0] == Card::new(ACE, SPADE));
+        assert!(cards[1] == Card::new(KING, CLUB));
+        assert!(*cards[2].value() == SEVEN);
+        assert!(parse_values("A7x").is_err());
+        assert!(parse_values(" ").is_err());
+        assert!(parse_cards("").unwrap().len() == 0);
+    }
+}
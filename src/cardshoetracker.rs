@@ -2,17 +2,31 @@ use cards::value::Value;
 use cards::value;
 use cards::card::Card;
 use shoe::shoe::DirectShoe;
+use shoe::shoe::ShoeEvent;
+use counting::CountingSystem;
 
 pub struct CardShoeTracker<'a> {
     counts: [[u32;4];13],
+    // Cards dealt this round, which stay seen through a mid round reshuffle
+    round_counts: [[u32;4];13],
     wrapping: &'a mut (DirectShoe + 'a),
 }
 
 impl <'a>DirectShoe for CardShoeTracker<'a> {
     fn pop(&mut self) -> Option<Card> {
+        let len = self.wrapping.len();
         let r = self.wrapping.pop();
         match r {
-            Some(ref r) => self.counts[r.value().index()][r.suit().index()] += 1,
+            Some(ref r) => {
+                // The shoe grew instead of losing a card: it ran out and the
+                // discards were shuffled back in, leaving out this round's
+                // cards.  Infinite shoes never change length
+                if self.wrapping.initial_length().is_some() && self.wrapping.len() >= len {
+                    self.counts = self.round_counts;
+                }
+                self.counts[r.value().index()][r.suit().index()] += 1;
+                self.round_counts[r.value().index()][r.suit().index()] += 1;
+            }
             None => (),
         };
         return r;
@@ -35,18 +49,34 @@ impl <'a>DirectShoe for CardShoeTracker<'a> {
     fn maximum_count_of_any_value(&self) -> Option<u32> {
         self.wrapping.maximum_count_of_any_value()
     }
+    fn end_round(&mut self) -> Option<ShoeEvent> {
+        let r = self.wrapping.end_round();
+        match r {
+            Some(ShoeEvent::Shuffled) => self.counts = [[0; 4]; 13],
+            Some(ShoeEvent::Returned(ref cards)) => {
+                for c in cards.iter() {
+                    // Cards taken with remove() were never counted
+                    let ref mut seen = self.counts[c.value().index()][c.suit().index()];
+                    if *seen > 0 {
+                        *seen -= 1;
+                    }
+                }
+            }
+            None => (),
+        };
+        self.round_counts = [[0; 4]; 13];
+        return r;
+    }
 }
 
 impl <'a>CardShoeTracker<'a> {
-    /*
-       TODO: Why does new not work?
-    pub fn new(wrap: &'a mut DirectShoe) -> CardShoeTracker<'a> {
+    pub fn new(wrap: &'a mut (DirectShoe + 'a)) -> CardShoeTracker<'a> {
         return CardShoeTracker {
-            counts: [[0, 0, 0, 0], ..13],
+            counts: [[0; 4]; 13],
+            round_counts: [[0; 4]; 13],
             wrapping: wrap,
         };
     }
-    */
     pub fn count_value(&self, ref v: Value) -> u32 {
         let mut ret = 0;
         for &i in self.counts[v.index()].iter() {
@@ -61,4 +91,83 @@ impl <'a>CardShoeTracker<'a> {
         }
         return ret;
     }
+    pub fn running_count(&self, system: &CountingSystem) -> f64 {
+        let mut ret = 0.0;
+        for v in value::VALUES.iter() {
+            ret += self.count_value(*v) as f64 * system.tag(v);
+        }
+        return ret;
+    }
+    // None for infinite shoes, where there's no true count to speak of
+    pub fn true_count(&self, system: &CountingSystem) -> Option<f64> {
+        match self.wrapping.initial_length() {
+            None => None,
+            Some(_) => Some(self.running_count(system) / (self.wrapping.len() as f64 / 52.0)),
+        }
+    }
+}
+
+#[test]
+fn test_tracker_resets_on_shuffle() {
+    use shoe::cutcardshoe::CutCardShoe;
+    use counting::HI_LO;
+    let mut shoe = CutCardShoe::new_decks(1, 0.5);
+    let mut tracker = CardShoeTracker::new(&mut shoe);
+    for _ in range(0, 10) {
+        tracker.pop().unwrap();
+    }
+    assert!(tracker.end_round().is_none());
+    assert_eq!(10, tracker.seen_cards());
+    for _ in range(0, 20) {
+        tracker.pop().unwrap();
+    }
+    assert_eq!(30, tracker.seen_cards());
+    assert!(tracker.true_count(&HI_LO).is_some());
+    assert!(tracker.end_round() == Some(ShoeEvent::Shuffled));
+    assert_eq!(0, tracker.seen_cards());
+    assert_eq!(0.0, tracker.running_count(&HI_LO));
+}
+
+#[test]
+fn test_tracker_reshuffle_mid_round() {
+    use shoe::cutcardshoe::CutCardShoe;
+    use counting::HI_LO;
+    // No cut card, so the shoe runs dry in the middle of the second round
+    let mut shoe = CutCardShoe::new_decks(1, 1.0);
+    let mut tracker = CardShoeTracker::new(&mut shoe);
+    for _ in range(0, 40) {
+        tracker.pop().unwrap();
+    }
+    assert!(tracker.end_round().is_none());
+    let mut round = Vec::new();
+    for _ in range(0, 13) {
+        round.push(tracker.pop().unwrap());
+    }
+    // The first 40 cards are back in the shoe, this round's aren't
+    assert_eq!(39, tracker.len());
+    assert_eq!(13, tracker.seen_cards());
+    let count = round.iter().fold(0.0, |sum, c| sum + HI_LO.tag(c.value()));
+    assert_eq!(count, tracker.running_count(&HI_LO));
+    // They're discards once the round is over, still out of the shoe
+    assert!(tracker.end_round().is_none());
+    assert_eq!(13, tracker.seen_cards());
+    tracker.pop().unwrap();
+    assert_eq!(14, tracker.seen_cards());
+}
+
+#[test]
+fn test_tracker_infinite_shoe() {
+    use shoe::randomshoe::new_infinite_shoe;
+    let mut shoe = new_infinite_shoe();
+    let mut tracker = CardShoeTracker::new(&mut shoe);
+    for _ in range(0, 5) {
+        tracker.pop().unwrap();
+    }
+    assert!(tracker.end_round().is_none());
+    for _ in range(0, 5) {
+        tracker.pop().unwrap();
+    }
+    assert!(tracker.end_round().is_none());
+    // The count carries on from round to round
+    assert_eq!(10, tracker.seen_cards());
 }
@@ -0,0 +1,74 @@
+use cards::value::Value;
+use hand::score_for_value;
+
+/**
+  A card counting tag table.  Tags are indexed by blackjack score - 1, so
+  the ace is first and all ten valued cards share the last tag.
+  */
+#[derive(Copy)]
+pub struct CountingSystem {
+    name: &'static str,
+    tags: [f64; 10],
+}
+
+impl CountingSystem {
+    pub fn new(name: &'static str, tags: [f64; 10]) -> CountingSystem {
+        CountingSystem {
+            name: name,
+            tags: tags,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn tags(&self) -> &[f64; 10] {
+        &self.tags
+    }
+
+    pub fn tag(&self, v: &Value) -> f64 {
+        self.tags[score_for_value(v) as usize - 1]
+    }
+
+    // The running count after a single full deck has been seen
+    pub fn full_deck_count(&self) -> f64 {
+        let mut ret = 0.0;
+        for i in range(0, 9) {
+            ret += 4.0 * self.tags[i];
+        }
+        ret + 16.0 * self.tags[9]
+    }
+
+    pub fn is_balanced(&self) -> bool {
+        self.full_deck_count() == 0.0
+    }
+}
+
+pub const HI_LO: CountingSystem = CountingSystem{
+    name: "hi-lo",
+    tags: [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, -1.0]};
+pub const HI_OPT_I: CountingSystem = CountingSystem{
+    name: "hi-opt-i",
+    tags: [0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, -1.0]};
+pub const KO: CountingSystem = CountingSystem{
+    name: "ko",
+    tags: [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, -1.0]};
+pub const WONG_HALVES: CountingSystem = CountingSystem{
+    name: "wong-halves",
+    tags: [-1.0, 0.5, 1.0, 1.0, 1.5, 1.0, 0.5, 0.0, -0.5, -1.0]};
+
+pub const SYSTEMS: [CountingSystem; 4] = [HI_LO, HI_OPT_I, KO, WONG_HALVES];
+
+#[test]
+fn test_counting_systems() {
+    use cards::value;
+    assert_eq!(-1.0, HI_LO.tag(&value::KING));
+    assert_eq!(1.0, HI_LO.tag(&value::FIVE));
+    assert_eq!(0.0, HI_OPT_I.tag(&value::ACE));
+    assert!(HI_LO.is_balanced());
+    assert!(HI_OPT_I.is_balanced());
+    assert!(WONG_HALVES.is_balanced());
+    assert!(!KO.is_balanced());
+    assert_eq!(4.0, KO.full_deck_count());
+}
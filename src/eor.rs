@@ -0,0 +1,261 @@
+use std::num::Float;
+use action_calculator::ActionCalculator;
+use bjaction::BJAction;
+use bjaction::BJAction::{HIT, STAND, DOUBLE, SPLIT};
+use cards::value::Value;
+use cards::value::{ACE, TWO, THREE, FOUR, FIVE, SIX, SEVEN, NINE, TEN};
+use counting::CountingSystem;
+use hand::SCORE_VALUES;
+use rules::BJRules;
+use shoe::shoe::DirectShoe;
+use shoe::composition::ShoeComposition;
+use strategy_export::{full_composition, insurance_value};
+
+// Each rank's share of a full deck, ace first
+const RANK_WEIGHTS: [f64; 10] = [1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 4.0];
+
+pub struct EffectOfRemoval {
+    // EV of the full shoe
+    pub base_ev: f64,
+    // Change in EV from removing a single card of each rank, ace first
+    pub eors: [f64; 10],
+}
+
+/**
+  Pearson correlation of the tags with per rank effects, each rank weighted by
+  how often it appears in a deck.
+  */
+pub fn correlation(tags: &[f64; 10], effects: &[f64; 10]) -> f64 {
+    let mut total_weight = 0.0;
+    let mut tag_mean = 0.0;
+    let mut effect_mean = 0.0;
+    for i in range(0, 10) {
+        total_weight += RANK_WEIGHTS[i];
+        tag_mean += RANK_WEIGHTS[i] * tags[i];
+        effect_mean += RANK_WEIGHTS[i] * effects[i];
+    }
+    tag_mean /= total_weight;
+    effect_mean /= total_weight;
+    let mut covariance = 0.0;
+    let mut tag_variance = 0.0;
+    let mut effect_variance = 0.0;
+    for i in range(0, 10) {
+        let t = tags[i] - tag_mean;
+        let e = effects[i] - effect_mean;
+        covariance += RANK_WEIGHTS[i] * t * e;
+        tag_variance += RANK_WEIGHTS[i] * t * t;
+        effect_variance += RANK_WEIGHTS[i] * e * e;
+    }
+    if tag_variance == 0.0 || effect_variance == 0.0 {
+        return 0.0;
+    }
+    covariance / (tag_variance * effect_variance).sqrt()
+}
+
+// Calls f once with the full shoe, then once with each rank removed
+fn for_each_removal<F>(num_decks: u32, mut f: F) where F: FnMut(Option<usize>, &mut DirectShoe) {
+    let mut cards = Vec::new();
+    let mut shoe = ShoeComposition::new_decks(num_decks).direct_shoe(&mut cards);
+    f(None, &mut shoe);
+    for i in range(0, 10) {
+        let removed = shoe.remove(&SCORE_VALUES[i]).unwrap();
+        f(Some(i), &mut shoe);
+        shoe.insert(&removed);
+    }
+}
+
+/**
+  The effect on the game's EV of removing one card of each rank from a full
+  shoe of num_decks decks.
+  */
+pub fn effect_of_removal(rules: &BJRules, num_decks: u32) -> EffectOfRemoval {
+    let mut ret = EffectOfRemoval {
+        base_ev: 0.0,
+        eors: [0.0; 10],
+    };
+    for_each_removal(num_decks, |removed, shoe| {
+        let ev = ActionCalculator::new(*rules, shoe).total_expected_best_value();
+        match removed {
+            None => ret.base_ev = ev,
+            Some(i) => ret.eors[i] = ev - ret.base_ev,
+        }
+    });
+    ret
+}
+
+pub fn betting_correlation(system: &CountingSystem, eor: &EffectOfRemoval) -> f64 {
+    correlation(system.tags(), &eor.eors)
+}
+
+// Change in an insurance bet's EV from removing one card of each rank
+pub fn insurance_eors(num_decks: u32) -> [f64; 10] {
+    let mut c = full_composition(num_decks);
+    let base = insurance_value(&c);
+    let mut ret = [0.0; 10];
+    for i in range(0, 10) {
+        c[i] -= 1;
+        ret[i] = insurance_value(&c) - base;
+        c[i] += 1;
+    }
+    ret
+}
+
+pub fn insurance_correlation(system: &CountingSystem, num_decks: u32) -> f64 {
+    correlation(system.tags(), &insurance_eors(num_decks))
+}
+
+/**
+  A playing decision between two actions.  Its EOR is the change in
+  EV(first) - EV(second) from removing one card of each rank.
+  */
+pub struct PlayingDecision {
+    pub player: Vec<Value>,
+    pub dealer_up: Value,
+    pub first: BJAction,
+    pub second: BJAction,
+}
+
+impl PlayingDecision {
+    pub fn new(player: Vec<Value>, dealer_up: Value, first: BJAction,
+               second: BJAction) -> PlayingDecision {
+        PlayingDecision {
+            player: player,
+            dealer_up: dealer_up,
+            first: first,
+            second: second,
+        }
+    }
+}
+
+// The decisions that carry most of the gain from varying play with the count
+pub fn key_decisions() -> Vec<PlayingDecision> {
+    vec![
+        PlayingDecision::new(vec![TEN, SIX], TEN, STAND, HIT),
+        PlayingDecision::new(vec![TEN, FIVE], TEN, STAND, HIT),
+        PlayingDecision::new(vec![TEN, TEN], FIVE, SPLIT, STAND),
+        PlayingDecision::new(vec![TEN, TEN], SIX, SPLIT, STAND),
+        PlayingDecision::new(vec![FOUR, SIX], TEN, DOUBLE, HIT),
+        PlayingDecision::new(vec![TEN, TWO], THREE, STAND, HIT),
+        PlayingDecision::new(vec![TEN, TWO], TWO, STAND, HIT),
+        PlayingDecision::new(vec![FIVE, SIX], ACE, DOUBLE, HIT),
+        PlayingDecision::new(vec![FOUR, FIVE], TWO, DOUBLE, HIT),
+        PlayingDecision::new(vec![FOUR, SIX], ACE, DOUBLE, HIT),
+        PlayingDecision::new(vec![TWO, SEVEN], SEVEN, DOUBLE, HIT),
+        PlayingDecision::new(vec![TEN, SIX], NINE, STAND, HIT),
+        PlayingDecision::new(vec![TEN, THREE], TWO, STAND, HIT),
+        PlayingDecision::new(vec![TEN, TWO], FOUR, STAND, HIT),
+        PlayingDecision::new(vec![TEN, TWO], FIVE, STAND, HIT),
+        PlayingDecision::new(vec![TEN, TWO], SIX, STAND, HIT),
+        PlayingDecision::new(vec![TEN, THREE], THREE, STAND, HIT),
+    ]
+}
+
+fn ev_of(evs: &Vec<(BJAction, f64)>, action: BJAction) -> Option<f64> {
+    evs.iter().find(|x| x.0 == action).map(|x| x.1)
+}
+
+/**
+  Average absolute correlation of the tags with the EOR of each decision.
+  Decisions where either action isn't legal under the rules are skipped.
+  */
+pub fn playing_efficiency(rules: &BJRules, num_decks: u32, system: &CountingSystem,
+                          decisions: &Vec<PlayingDecision>) -> f64 {
+    // Indexed by decision, then by removed rank
+    let mut bases: Vec<Option<f64>> = Vec::new();
+    let mut effects: Vec<[f64; 10]> = Vec::new();
+    for_each_removal(num_decks, |removed, shoe| {
+        let mut calc = ActionCalculator::new(*rules, shoe);
+        for (d, decision) in decisions.iter().enumerate() {
+            let difference = match calc.action_values(&decision.player, &decision.dealer_up) {
+                Some(evs) => match (ev_of(&evs, decision.first), ev_of(&evs, decision.second)) {
+                    (Some(f), Some(s)) => Some(f - s),
+                    _ => None,
+                },
+                None => None,
+            };
+            match removed {
+                None => {
+                    bases.push(difference);
+                    effects.push([0.0; 10]);
+                }
+                Some(i) => match (bases[d], difference) {
+                    (Some(b), Some(v)) => effects[d][i] = v - b,
+                    _ => bases[d] = None,
+                },
+            }
+        }
+    });
+    let mut total = 0.0;
+    let mut count = 0;
+    for (d, e) in effects.iter().enumerate() {
+        if bases[d].is_some() {
+            total += correlation(system.tags(), e).abs();
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return 0.0;
+    }
+    total / count as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::Float;
+    use eor::{EffectOfRemoval, betting_correlation, insurance_correlation, correlation};
+    use eor::{effect_of_removal, playing_efficiency, key_decisions, PlayingDecision};
+    use counting::{HI_LO, HI_OPT_I};
+    use rules::BJRules;
+    use bjaction::BJAction::{STAND, SPLIT};
+    use cards::value::{TEN, SIX};
+
+    #[test]
+    #[ignore]
+    fn test_effect_of_removal() {
+        let eor = effect_of_removal(&BJRules::new(), 1);
+        // Taking out aces and tens hurts the player, taking out a 5 helps most
+        assert!(eor.eors[0] < 0.0);
+        assert!(eor.eors[9] < 0.0);
+        for i in range(0, 10) {
+            assert!(i == 4 || eor.eors[i] < eor.eors[4]);
+        }
+        assert!(eor.eors[4] > 0.0);
+    }
+
+    #[test]
+    fn test_playing_efficiency() {
+        // Standing on 16 against a ten gets better as tens are left in
+        let decisions = key_decisions().into_iter().take(1).collect();
+        let pe = playing_efficiency(&BJRules::new(), 1, &HI_LO, &decisions);
+        assert!(pe > 0.5 && pe <= 1.0);
+        // No decision to make when splitting isn't allowed
+        let decisions = vec![PlayingDecision::new(vec![TEN, SIX], TEN, SPLIT, STAND)];
+        assert_eq!(0.0, playing_efficiency(&BJRules::new(), 1, &HI_LO, &decisions));
+    }
+
+    #[test]
+    fn test_betting_correlation() {
+        // Single deck EORs, in percent
+        let eor = EffectOfRemoval {
+            base_ev: 0.0,
+            eors: [-0.61, 0.38, 0.44, 0.55, 0.69, 0.46, 0.28, 0.0, -0.18, -0.51],
+        };
+        let bc = betting_correlation(&HI_LO, &eor);
+        assert!(bc > 0.96 && bc < 0.98);
+    }
+
+    #[test]
+    fn test_insurance_correlation() {
+        let ic = insurance_correlation(&HI_LO, 6);
+        assert!(ic > 0.75 && ic < 0.77);
+        // Hi-opt I doesn't count aces, so it's better at insurance
+        assert!(insurance_correlation(&HI_OPT_I, 6) > ic);
+    }
+
+    #[test]
+    fn test_correlation() {
+        let tags = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0];
+        assert_eq!(1.0, (correlation(&tags, &tags) * 1000000.0).round() / 1000000.0);
+        assert_eq!(0.0, correlation(&tags, &[0.0; 10]));
+    }
+}
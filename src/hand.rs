@@ -2,14 +2,22 @@ use cards::value;
 use cards::value::Value;
 use cards::card::Card;
 use std::fmt;
+use std::str::FromStr;
+use cards::parse::CardParseError;
+use cards::parse::parse_cards;
 use shoe::shoe::DirectShoe;
 
 pub const INDEX_TO_SCORE: [u32;13] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 10, 10, 10];
 
+// One value for each distinct blackjack score, ace first
+pub const SCORE_VALUES: [Value;10] = [value::ACE, value::TWO, value::THREE, value::FOUR,
+    value::FIVE, value::SIX, value::SEVEN, value::EIGHT, value::NINE, value::TEN];
+
 pub fn score_for_value(v: &Value) -> u32 {
     return INDEX_TO_SCORE[v.index()];
 }
 
+#[derive(Clone)]
 pub struct BJHand {
     score: u32,
     ace_count: u32,
@@ -88,6 +96,20 @@ impl BJHand {
         ret
     }
 
+    /**
+      Moves the card split off this hand into a hand of its own, so both can
+      be played at the table.  Call after split().
+      */
+    pub fn take_split_hand(&mut self) -> BJHand {
+        assert!(self.splits_to_solve.len() > 0);
+        let card = self.splits_to_solve.pop().unwrap();
+        self.splits_done += 1;
+        let mut ret = BJHand::new();
+        ret.splits_done = self.splits_done;
+        ret.add_card(&card);
+        ret
+    }
+
     pub fn without_split_information(&self) -> BJHand {
         let mut ret = BJHand::new_with_cards(&self.cards);
         // For redoubles
@@ -196,6 +218,14 @@ impl BJHand {
     }
 }
 
+// Cards like A7, T-6 or AsKd, see parse_cards()
+impl FromStr for BJHand {
+    type Err = CardParseError;
+    fn from_str(s: &str) -> Result<BJHand, CardParseError> {
+        parse_cards(s).map(|cards| BJHand::new_with_cards(&cards))
+    }
+}
+
 #[test]
 fn test_hand() {
     use cards::suit;
@@ -222,3 +252,12 @@ fn test_hand() {
     assert_eq!(false, h.is_soft());
     assert_eq!(4, h.len());
 }
+
+#[test]
+fn test_parse_hand() {
+    let h = "A-7".parse::<BJHand>().unwrap();
+    assert_eq!(18, h.score());
+    assert!(h.is_soft());
+    assert_eq!(21, "AsKd".parse::<BJHand>().unwrap().score());
+    assert!("A-Z".parse::<BJHand>().is_err());
+}
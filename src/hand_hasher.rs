@@ -11,6 +11,7 @@ use hand::BJHand;
 use std::collections::Bitv;
 use std::num::Int;
 use shoe::shoe::DirectShoe;
+use std::cmp;
 
 use self::num::Zero;
 use self::num::Integer;
@@ -143,7 +144,6 @@ impl PlayerHandHasher {
             false => 0,
         });
 
-        // TODO: Include rules.automatic_win_at_hand_length()
         // Number of cards in the hand only matter if it's 1, 2, or 3+
         // Note: Number of cards in the hand actually don't matter, since it's
         //       covered by the rules:  For example, can_double(), etc may
@@ -177,6 +177,14 @@ impl PlayerHandHasher {
                 rules.split_limit() as u64 + 1,
                 hand.splits_to_solve() as u64));
         }
+        // A five card trick scores differently from any other hand with
+        // its total, even with the same actions left, and the closer a hand
+        // is to one the more its next cards are worth
+        let trick = rules.automatic_win_at_hand_length();
+        if trick > 0 {
+            v.push(HashRange::new(trick as u64 + 1,
+                                  cmp::min(hand.len(), trick) as u64));
+        }
         if include_actions {
             let actions = [STAND, HIT, DOUBLE, SPLIT, SURRENDER];
             for &action in actions.iter() {
@@ -0,0 +1,421 @@
+use std::fmt;
+use bjaction::BJAction;
+use bjaction::BJAction::STAND;
+use bjaction::BJ_ACTIONS;
+use cards::card::Card;
+use cards::value::Value;
+use cards::parse::parse_cards;
+use round::{Round, RoundState, RoundError};
+use rules::RuleSet;
+use shoe::shoe::DirectShoe;
+use shoe::shoe::ShoeEvent;
+use shoe::directshoe::DirectActualShoe;
+
+/**
+  Makes the decisions of a round being recorded.
+  */
+pub trait Player {
+    fn insurance(&mut self, round: &Round) -> bool;
+    fn action(&mut self, round: &Round) -> BJAction;
+}
+
+/**
+  Everything needed to deal a round again: the cards in the order they came
+  out of the shoe, and every decision.  shoe_position is how many cards had
+  been dealt from the shoe before the round, or 0 for infinite shoes.
+  */
+#[derive(Clone)]
+pub struct RoundRecord {
+    pub shoe_position: u32,
+    pub wager: f64,
+    pub cards: Vec<Card>,
+    pub insurance: Option<bool>,
+    pub actions: Vec<BJAction>,
+    pub result: f64,
+    // The line of the text it was parsed from, for errors about it
+    pub line: Option<usize>,
+}
+
+// Where a record was read from isn't part of the round
+impl PartialEq for RoundRecord {
+    fn eq(&self, other: &RoundRecord) -> bool {
+        self.shoe_position == other.shoe_position && self.wager == other.wager &&
+            self.cards == other.cards && self.insurance == other.insurance &&
+            self.actions == other.actions && self.result == other.result
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct HistoryError {
+    // 1 based, or 0 when the problem isn't on any one line
+    pub line: usize,
+    pub reason: String,
+}
+
+impl HistoryError {
+    pub fn new(line: usize, reason: &str) -> HistoryError {
+        HistoryError {
+            line: line,
+            reason: String::from_str(reason),
+        }
+    }
+}
+
+impl fmt::Show for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+// Remembers every card popped from the shoe it wraps
+struct RecordingShoe<'a> {
+    wrapping: &'a mut (DirectShoe + 'a),
+    drawn: Vec<Card>,
+}
+
+impl <'a>DirectShoe for RecordingShoe<'a> {
+    fn pop(&mut self) -> Option<Card> {
+        let r = self.wrapping.pop();
+        match r {
+            Some(c) => self.drawn.push(c),
+            None => (),
+        };
+        r
+    }
+    fn len(&self) -> usize {
+        self.wrapping.len()
+    }
+    fn count(&self, v: &Value) -> u32 {
+        self.wrapping.count(v)
+    }
+    fn remove(&mut self, v: &Value) -> Option<Card> {
+        self.wrapping.remove(v)
+    }
+    fn insert(&mut self, v: &Card) {
+        self.wrapping.insert(v)
+    }
+    fn initial_length(&self) -> Option<u32> {
+        self.wrapping.initial_length()
+    }
+    fn maximum_count_of_any_value(&self) -> Option<u32> {
+        self.wrapping.maximum_count_of_any_value()
+    }
+    fn end_round(&mut self) -> Option<ShoeEvent> {
+        self.wrapping.end_round()
+    }
+}
+
+/**
+  Plays one round from the shoe with the player's decisions and records it.
+  Like Round, this leaves calling end_round() on the shoe to the caller.
+  */
+pub fn play_round<'a>(rule_set: &RuleSet, shoe: &'a mut (DirectShoe + 'a), wager: f64,
+                      player: &mut Player) -> Result<RoundRecord, RoundError> {
+    let shoe_position = match shoe.initial_length() {
+        Some(l) => l - shoe.len() as u32,
+        None => 0,
+    };
+    let mut recording = RecordingShoe {
+        wrapping: shoe,
+        drawn: Vec::new(),
+    };
+    let (insurance, actions, result) = {
+        let mut round = try!(Round::deal(rule_set.rules, &mut recording, wager));
+        let mut insurance = None;
+        if round.state() == RoundState::Insurance {
+            let take = player.insurance(&round);
+            try!(round.take_insurance(take));
+            insurance = Some(take);
+        }
+        let mut actions = Vec::new();
+        while round.state() == RoundState::PlayerTurn {
+            let a = player.action(&round);
+            try!(round.act(a));
+            actions.push(a);
+        }
+        (insurance, actions, round.net_result().unwrap())
+    };
+    Ok(RoundRecord {
+        shoe_position: shoe_position,
+        wager: wager,
+        cards: recording.drawn,
+        insurance: insurance,
+        actions: actions,
+        result: result,
+        line: None,
+    })
+}
+
+// Makes the decisions of a recorded round over again
+struct ReplayPlayer<'a> {
+    record: &'a RoundRecord,
+    next: usize,
+}
+
+impl <'a>Player for ReplayPlayer<'a> {
+    fn insurance(&mut self, _: &Round) -> bool {
+        self.record.insurance.unwrap_or(false)
+    }
+    fn action(&mut self, _: &Round) -> BJAction {
+        // Running out shows up as a mismatch once the round is over
+        let ret = self.record.actions.get(self.next).map(|a| *a).unwrap_or(STAND);
+        self.next += 1;
+        ret
+    }
+}
+
+/**
+  Deals the recorded cards again, in order, and makes the same decisions.
+  Errors if the round doesn't come out exactly as recorded.
+  */
+pub fn replay_round(rule_set: &RuleSet, record: &RoundRecord) -> Result<(), String> {
+    let mut cards: Vec<Card> = record.cards.iter().rev().map(|c| *c).collect();
+    let mut shoe = DirectActualShoe {
+        initial_length: Some(cards.len() as u32),
+        maximum_count_of_any_value: Some(cards.len() as u32),
+        cards: &mut cards,
+    };
+    let mut player = ReplayPlayer {
+        record: record,
+        next: 0,
+    };
+    let replayed = match play_round(rule_set, &mut shoe, record.wager, &mut player) {
+        Ok(r) => r,
+        Err(e) => return Err(format!("replay failed: {:?}", e)),
+    };
+    if replayed.cards != record.cards {
+        return Err(format!("replay used {} of {} recorded cards", replayed.cards.len(),
+                           record.cards.len()));
+    }
+    if replayed.actions != record.actions || replayed.insurance != record.insurance {
+        return Err(String::from_str("replay didn't take the recorded decisions"));
+    }
+    if replayed.result != record.result {
+        return Err(format!("replay won {} but {} was recorded", replayed.result, record.result));
+    }
+    Ok(())
+}
+
+/**
+  A session of rounds under one rule set.  The text form has a rules line
+  and then a line per round:
+
+      rules 6D S17 DAS SPL3 DBL1 BJ3:2
+      round pos=0 wager=1 cards=Ts9h9d8c insurance=- actions=STD result=1
+
+  Blank lines and lines starting with # are ignored.
+  */
+pub struct HandHistory {
+    pub rule_set: RuleSet,
+    pub rounds: Vec<RoundRecord>,
+}
+
+impl HandHistory {
+    pub fn new(rule_set: RuleSet) -> HandHistory {
+        HandHistory {
+            rule_set: rule_set,
+            rounds: Vec::new(),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<HandHistory, HistoryError> {
+        let mut ret: Option<HandHistory> = None;
+        for (i, raw) in s.lines().enumerate() {
+            let line = raw.trim();
+            if line.len() == 0 || line.starts_with("#") {
+                continue;
+            }
+            if line.starts_with("rules ") {
+                if ret.is_some() {
+                    return Err(HistoryError::new(i + 1, "rules given twice"));
+                }
+                let rule_set = try!(RuleSet::parse(&line[6..]).map_err(
+                    |e| HistoryError::new(i + 1, format!("{:?}", e).as_slice())));
+                ret = Some(HandHistory::new(rule_set));
+            } else if line.starts_with("round ") {
+                let mut record = try!(parse_round(&line[6..]).map_err(
+                    |e| HistoryError::new(i + 1, e.as_slice())));
+                record.line = Some(i + 1);
+                match ret {
+                    Some(ref mut h) => h.rounds.push(record),
+                    None => return Err(HistoryError::new(i + 1, "a round before the rules")),
+                }
+            } else {
+                return Err(HistoryError::new(i + 1, "expected a rules or round line"));
+            }
+        }
+        ret.ok_or(HistoryError::new(0, "no rules line"))
+    }
+
+    /**
+      The line a round was read from, or for rounds that weren't read from
+      text, its line as this prints it after the rules.
+      */
+    pub fn round_line(&self, round: usize) -> usize {
+        self.rounds[round].line.unwrap_or(round + 2)
+    }
+
+    // Replays every round, stopping at the first one that comes out differently
+    pub fn replay(&self) -> Result<(), HistoryError> {
+        for (i, r) in self.rounds.iter().enumerate() {
+            try!(replay_round(&self.rule_set, r).map_err(
+                |e| HistoryError::new(self.round_line(i), e.as_slice())));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Show for HandHistory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "rules {:?}", self.rule_set));
+        for r in self.rounds.iter() {
+            try!(writeln!(f, "round {:?}", r));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Show for RoundRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cards: Vec<String> = self.cards.iter().map(|c| format!("{:?}", c)).collect();
+        let actions: Vec<String> = self.actions.iter().map(|a| format!("{:?}", a)).collect();
+        write!(f, "pos={} wager={} cards={} insurance={} actions={} result={}",
+               self.shoe_position, self.wager, cards.concat(),
+               match self.insurance {
+                   Some(true) => "y",
+                   Some(false) => "n",
+                   None => "-",
+               },
+               if actions.len() == 0 { String::from_str("-") } else { actions.connect(",") },
+               self.result)
+    }
+}
+
+fn parse_action(s: &str) -> Option<BJAction> {
+    BJ_ACTIONS.iter().find(|a| format!("{:?}", a).as_slice() == s).map(|a| *a)
+}
+
+fn parse_round(s: &str) -> Result<RoundRecord, String> {
+    let mut ret = RoundRecord {
+        shoe_position: 0,
+        wager: 0.0,
+        cards: Vec::new(),
+        insurance: None,
+        actions: Vec::new(),
+        result: 0.0,
+        line: None,
+    };
+    let mut fields = 0;
+    for field in s.split(' ').filter(|f| f.len() > 0) {
+        let parts: Vec<&str> = field.splitn(1, '=').collect();
+        if parts.len() != 2 {
+            return Err(format!("expected key=value, not {}", field));
+        }
+        let (key, value) = (parts[0], parts[1]);
+        let bad = format!("bad {} {}", key, value);
+        match key {
+            "pos" => ret.shoe_position = try!(value.parse::<u32>().ok().ok_or(bad)),
+            "wager" => ret.wager = try!(value.parse::<f64>().ok().ok_or(bad)),
+            "result" => ret.result = try!(value.parse::<f64>().ok().ok_or(bad)),
+            "cards" => ret.cards = try!(parse_cards(value).map_err(|e| format!("{:?}", e))),
+            "insurance" => ret.insurance = match value {
+                "y" => Some(true),
+                "n" => Some(false),
+                "-" => None,
+                _ => return Err(format!("bad insurance {}", value)),
+            },
+            "actions" => if value != "-" {
+                for a in value.split(',') {
+                    match parse_action(a) {
+                        Some(action) => ret.actions.push(action),
+                        None => return Err(format!("unknown action {}", a)),
+                    }
+                }
+            },
+            _ => return Err(format!("unknown field {}", key)),
+        }
+        fields += 1;
+    }
+    if fields != 6 {
+        return Err(String::from_str("a round needs pos, wager, cards, insurance, actions and result"));
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use history::{HandHistory, Player, RoundRecord, play_round};
+    use bjaction::BJAction;
+    use bjaction::BJAction::{HIT, STAND, SPLIT};
+    use cards::parse::parse_cards;
+    use round::Round;
+    use rules::RuleSet;
+    use shoe::directshoe::DirectActualShoe;
+
+    // Splits pairs, hits below 17
+    struct SimplePlayer;
+
+    impl Player for SimplePlayer {
+        fn insurance(&mut self, _: &Round) -> bool {
+            false
+        }
+        fn action(&mut self, round: &Round) -> BJAction {
+            if round.legal_actions().contains(&SPLIT) {
+                SPLIT
+            } else if round.active_hand().unwrap().hand().score() < 17 {
+                HIT
+            } else {
+                STAND
+            }
+        }
+    }
+
+    fn record(cards: &str) -> RoundRecord {
+        let mut cards: Vec<_> = parse_cards(cards).unwrap().into_iter().rev().collect();
+        let mut shoe = DirectActualShoe {
+            initial_length: Some(52),
+            maximum_count_of_any_value: Some(4),
+            cards: &mut cards,
+        };
+        let rule_set = RuleSet::parse("S17 DAS SPL3").unwrap();
+        play_round(&rule_set, &mut shoe, 1.0, &mut SimplePlayer).unwrap()
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        // The first 8 busts, the second pushes the dealer's 18
+        let r = record("8s6h8dTc5cTdKh2s");
+        assert_eq!(vec![SPLIT, HIT, STAND], r.actions);
+        assert_eq!(-1.0, r.result);
+        assert_eq!(44, r.shoe_position);
+        let mut history = HandHistory::new(RuleSet::parse("6D S17 DAS SPL3").unwrap());
+        history.rounds.push(r);
+        let text = format!("{:?}", history);
+        assert_eq!("rules 6D S17 DAS SPL3 DBL1 BJ3:2\n\
+                    round pos=44 wager=1 cards=8s6h8dTc5cTdKh2s insurance=- \
+                    actions=SPT,HIT,STD result=-1\n", text);
+        let parsed = HandHistory::parse(text.as_slice()).unwrap();
+        assert!(parsed.rounds == history.rounds);
+        assert!(parsed.replay().is_ok());
+    }
+
+    #[test]
+    fn test_replay_catches_changes() {
+        // The dealer's 17 beats 16, not the other way around
+        let text = "rules S17\n\
+                    round pos=0 wager=1 cards=Ts7h6dTc insurance=- actions=STD result=1\n";
+        let history = HandHistory::parse(text).unwrap();
+        let e = history.replay().err().unwrap();
+        assert_eq!(2, e.line);
+        assert!(HandHistory::parse("round pos=0").is_err());
+        let e = HandHistory::parse("rules S17\nround pos=0 wager=1").err().unwrap();
+        assert_eq!(2, e.line);
+        // Lines in the file, comments and blank lines included
+        let text = "# A session\n\
+                    rules S17\n\
+                    \n\
+                    round pos=0 wager=1 cards=Ts7h6dTc insurance=- actions=STD result=1\n";
+        let e = HandHistory::parse(text).unwrap().replay().err().unwrap();
+        assert_eq!(4, e.line);
+    }
+}
@@ -0,0 +1,123 @@
+use std::ascii::AsciiExt;
+use std::fmt;
+use std::str::FromStr;
+use cards::parse::CardParseError;
+use cards::value::Value;
+use hand::SCORE_VALUES;
+use hand::score_for_value;
+
+/**
+  What the player knows about the dealer's hole card: the ranks it could
+  be.  Ranks are indexed by score - 1, so every ten valued card is one rank.
+  */
+#[derive(Copy, Clone, PartialEq)]
+pub struct HoleCardHint {
+    possible: [bool; 10],
+}
+
+impl HoleCardHint {
+    pub fn new_unknown() -> HoleCardHint {
+        HoleCardHint {
+            possible: [true; 10],
+        }
+    }
+
+    pub fn new_exact(v: &Value) -> HoleCardHint {
+        HoleCardHint::new_from_values(&[*v])
+    }
+
+    pub fn new_from_values(values: &[Value]) -> HoleCardHint {
+        let mut possible = [false; 10];
+        for v in values.iter() {
+            possible[score_for_value(v) as usize - 1] = true;
+        }
+        HoleCardHint {
+            possible: possible,
+        }
+    }
+
+    // Scores low to high inclusive, the ace counting 1
+    pub fn new_score_range(low: u32, high: u32) -> HoleCardHint {
+        let mut possible = [false; 10];
+        for i in range(0, 10) {
+            possible[i] = low <= i as u32 + 1 && i as u32 + 1 <= high;
+        }
+        HoleCardHint {
+            possible: possible,
+        }
+    }
+
+    pub fn new_ten_value() -> HoleCardHint {
+        HoleCardHint::new_score_range(10, 10)
+    }
+
+    pub fn allows(&self, v: &Value) -> bool {
+        self.possible[score_for_value(v) as usize - 1]
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.possible.iter().all(|p| *p)
+    }
+}
+
+// "any", or the possible ranks like "T" or "2345678"
+impl fmt::Show for HoleCardHint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_unknown() {
+            return "any".fmt(f);
+        }
+        let ranks: String = SCORE_VALUES.iter().filter(|v| self.allows(*v)).map(
+            |v| v.char()).collect();
+        ranks.fmt(f)
+    }
+}
+
+/**
+  "any", or ranks and rank ranges separated by commas or spaces: "T",
+  "2-8" or "A,T".
+  */
+impl FromStr for HoleCardHint {
+    type Err = CardParseError;
+    fn from_str(s: &str) -> Result<HoleCardHint, CardParseError> {
+        let t = s.trim();
+        if t.eq_ignore_ascii_case("any") {
+            return Ok(HoleCardHint::new_unknown());
+        }
+        let mut possible = [false; 10];
+        for part in t.split(|c: char| c == ',' || c.is_whitespace()).filter(|p| p.len() > 0) {
+            let ends: Vec<&str> = part.splitn(1, '-').collect();
+            let low = score_for_value(&try!(ends[0].parse::<Value>()));
+            let high = match ends.get(1) {
+                Some(h) => score_for_value(&try!(h.parse::<Value>())),
+                None => low,
+            };
+            if low > high {
+                return Err(CardParseError::new(part, "a range goes from low to high"));
+            }
+            for score in range(low, high + 1) {
+                possible[score as usize - 1] = true;
+            }
+        }
+        if !possible.iter().any(|p| *p) {
+            return Err(CardParseError::new(s, "expected hole card ranks like T or 2-8"));
+        }
+        Ok(HoleCardHint {
+            possible: possible,
+        })
+    }
+}
+
+#[test]
+fn test_parse_hole_card_hint() {
+    use cards::value::{ACE, TWO, EIGHT, NINE, KING};
+    let h = "2-8".parse::<HoleCardHint>().unwrap();
+    assert!(h.allows(&TWO) && h.allows(&EIGHT));
+    assert!(!h.allows(&ACE) && !h.allows(&NINE));
+    assert!(h == HoleCardHint::new_score_range(2, 8));
+    assert_eq!("2345678", format!("{:?}", h));
+    assert!("t".parse::<HoleCardHint>().unwrap().allows(&KING));
+    assert!("A, T".parse::<HoleCardHint>().unwrap() == HoleCardHint::new_from_values(&[ACE, KING]));
+    assert!("any".parse::<HoleCardHint>().unwrap().is_unknown());
+    assert!("8-2".parse::<HoleCardHint>().is_err());
+    assert!("".parse::<HoleCardHint>().is_err());
+}
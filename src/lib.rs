@@ -3,12 +3,17 @@ pub mod cards {
     pub mod color;
     pub mod value;
     pub mod card;
+    pub mod parse;
 }
 pub mod shoe {
     pub mod deck;
     pub mod shoe;
     pub mod directshoe;
     pub mod randomshoe;
+    pub mod composition;
+    pub mod cutcardshoe;
+    pub mod csmshoe;
+    pub mod shuffle;
 }
 pub mod hand;
 pub mod cardshoetracker;
@@ -17,3 +22,16 @@ pub mod action_calculator;
 pub mod rules;
 pub mod hand_hasher;
 pub mod hash_database;
+pub mod counting;
+pub mod strategy_export;
+pub mod eor;
+pub mod outcome;
+pub mod bank;
+pub mod betramp;
+pub mod round;
+pub mod history;
+pub mod audit;
+pub mod hole_card;
+pub mod payoff;
+pub mod table;
+pub mod report;
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+use std::num::Float;
+
+// Outcomes are kept in hundredths of a unit so 6:5 and 3:2 payouts both fit
+const RESOLUTION: f64 = 100.0;
+
+/**
+  Probability of each net result of a round, in units of the initial bet.
+  */
+#[derive(Clone)]
+pub struct OutcomeDistribution {
+    probs: BTreeMap<i64, f64>,
+}
+
+impl OutcomeDistribution {
+    pub fn new() -> OutcomeDistribution {
+        OutcomeDistribution {
+            probs: BTreeMap::new(),
+        }
+    }
+
+    pub fn new_point(units: f64) -> OutcomeDistribution {
+        let mut ret = OutcomeDistribution::new();
+        ret.add(units, 1.0);
+        ret
+    }
+
+    pub fn add(&mut self, units: f64, probability: f64) {
+        if probability == 0.0 {
+            return;
+        }
+        let key = (units * RESOLUTION).round() as i64;
+        let current = match self.probs.get(&key) {
+            Some(p) => *p,
+            None => 0.0,
+        };
+        self.probs.insert(key, current + probability);
+    }
+
+    // Adds every outcome of other, scaled by weight
+    pub fn mix(&mut self, other: &OutcomeDistribution, weight: f64) {
+        for (k, p) in other.probs.iter() {
+            self.add(*k as f64 / RESOLUTION, *p * weight);
+        }
+    }
+
+    pub fn probability(&self, units: f64) -> f64 {
+        match self.probs.get(&((units * RESOLUTION).round() as i64)) {
+            Some(p) => *p,
+            None => 0.0,
+        }
+    }
+
+    // (units, probability) pairs from the worst outcome to the best
+    pub fn outcomes(&self) -> Vec<(f64, f64)> {
+        self.probs.iter().map(|(k, p)| (*k as f64 / RESOLUTION, *p)).collect()
+    }
+
+    pub fn total_probability(&self) -> f64 {
+        self.probs.values().fold(0.0, |sum, p| sum + *p)
+    }
+
+    fn central_moment(&self, n: i32) -> f64 {
+        let mean = self.mean();
+        self.outcomes().iter().fold(0.0, |sum, &(u, p)| sum + p * (u - mean).powi(n))
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.outcomes().iter().fold(0.0, |sum, &(u, p)| sum + p * u)
+    }
+
+    pub fn variance(&self) -> f64 {
+        self.central_moment(2)
+    }
+
+    pub fn standard_deviation(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    pub fn skewness(&self) -> f64 {
+        match self.standard_deviation() {
+            0.0 => 0.0,
+            sd => self.central_moment(3) / sd.powi(3),
+        }
+    }
+
+    // Odds of netting at least units
+    pub fn probability_at_least(&self, units: f64) -> f64 {
+        let key = units * RESOLUTION - 1e-6;
+        self.probs.iter().filter(|&(k, _)| *k as f64 >= key).fold(0.0, |sum, (_, p)| sum + *p)
+    }
+
+    pub fn probability_of_winning(&self) -> f64 {
+        self.outcomes().iter().filter(|x| x.0 > 0.0).fold(0.0, |sum, x| sum + x.1)
+    }
+
+    pub fn probability_of_losing(&self) -> f64 {
+        self.outcomes().iter().filter(|x| x.0 < 0.0).fold(0.0, |sum, x| sum + x.1)
+    }
+}
+
+#[test]
+fn test_outcome_distribution() {
+    let mut d = OutcomeDistribution::new();
+    d.add(1.0, 0.5);
+    d.add(-1.0, 0.5);
+    assert_eq!(0.0, d.mean());
+    assert_eq!(1.0, d.variance());
+    assert_eq!(0.0, d.skewness());
+    let mut mixed = OutcomeDistribution::new_point(1.5);
+    mixed.mix(&d, 1.0);
+    assert_eq!(2.0, mixed.total_probability());
+    assert_eq!(1.0, mixed.probability(1.5));
+    assert_eq!(1.5, mixed.probability_of_winning());
+    assert_eq!(3, mixed.outcomes().len());
+    assert_eq!(1.5, mixed.probability_at_least(1.0));
+    assert_eq!(1.5, mixed.probability_at_least(1.5));
+    assert_eq!(2.0, mixed.probability_at_least(-1.0));
+}
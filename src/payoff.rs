@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::num::Float;
+use std::str::FromStr;
+use outcome::OutcomeDistribution;
+
+// Nets are kept in hundredths of a unit, like OutcomeDistribution
+const RESOLUTION: f64 = 100.0;
+
+/**
+  What a net result is really worth to the player, in units of the initial
+  bet.  Casino deals and tournaments make this something other than the
+  units won.  Coupons and chips are valued on the round's net, as if they
+  went with the first hand.
+  */
+#[derive(Copy, Clone, PartialEq)]
+pub enum Payoff {
+    Units,
+    // 1 for netting at least this many units and 0 otherwise, for tournaments
+    AtLeast(f64),
+    // This fraction of a net loss is given back
+    LossRebate(f64),
+    // A non-negotiable chip as the initial bet: wins are paid in cash and
+    // the chip is lost in place of the first unit lost.  A push keeps the
+    // chip, worth the given units.
+    PromoChip(f64),
+    // A match play coupon beside the initial bet wins one more unit and is
+    // lost on a loss.  A push keeps the coupon, worth the given units.
+    MatchPlay(f64),
+}
+
+impl Payoff {
+    pub fn value(&self, net: f64) -> f64 {
+        match *self {
+            Payoff::Units => net,
+            Payoff::AtLeast(need) => if net >= need - 1e-8 { 1.0 } else { 0.0 },
+            Payoff::LossRebate(rebate) => if net < 0.0 { net * (1.0 - rebate) } else { net },
+            Payoff::PromoChip(push) => {
+                if net > 0.0 {
+                    net
+                } else if net == 0.0 {
+                    push
+                } else if net > -1.0 {
+                    0.0
+                } else {
+                    net + 1.0
+                }
+            }
+            Payoff::MatchPlay(push) => {
+                if net > 0.0 { net + 1.0 } else if net == 0.0 { push } else { net }
+            }
+        }
+    }
+
+    pub fn expected(&self, d: &OutcomeDistribution) -> f64 {
+        d.outcomes().iter().fold(0.0, |sum, &(units, p)| sum + p * self.value(units))
+    }
+}
+
+// units, at_least:N, rebate:R, promo:P or match:P, the same as FromStr takes
+impl fmt::Show for Payoff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Payoff::Units => write!(f, "units"),
+            Payoff::AtLeast(need) => write!(f, "at_least:{}", need),
+            Payoff::LossRebate(rebate) => write!(f, "rebate:{}", rebate),
+            Payoff::PromoChip(push) => write!(f, "promo:{}", push),
+            Payoff::MatchPlay(push) => write!(f, "match:{}", push),
+        }
+    }
+}
+
+impl FromStr for Payoff {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Payoff, String> {
+        let parts: Vec<&str> = s.trim().splitn(1, ':').collect();
+        let parameter = match parts.get(1) {
+            Some(p) => Some(try!(p.trim().parse::<f64>().ok().ok_or(
+                format!("Bad number {} in payoff {}", p, s)))),
+            None => None,
+        };
+        match (parts[0], parameter) {
+            ("units", None) => Ok(Payoff::Units),
+            ("at_least", Some(need)) => Ok(Payoff::AtLeast(need)),
+            ("rebate", Some(rebate)) if rebate >= 0.0 && rebate <= 1.0 => Ok(Payoff::LossRebate(rebate)),
+            ("promo", Some(push)) => Ok(Payoff::PromoChip(push)),
+            ("match", Some(push)) => Ok(Payoff::MatchPlay(push)),
+            _ => Err(format!("Unknown payoff {}: expected units, at_least:N, rebate:R, promo:P or match:P", s)),
+        }
+    }
+}
+
+/**
+  When to stop a session of up to max_rounds rounds whose net is paid
+  through a payoff, like a loss rebate on the whole session.  Every round
+  has the same outcome distribution, so the play within a round doesn't
+  change with the session's net.  The player quits whenever the payoff of
+  stopping is at least what playing on is worth.
+  */
+pub struct SessionPlan {
+    // Indexed by rounds played: nets in hundredths to (value, quit)
+    states: Vec<BTreeMap<i64, (f64, bool)>>,
+}
+
+impl SessionPlan {
+    pub fn new(round: &OutcomeDistribution, payoff: Payoff, max_rounds: u32) -> SessionPlan {
+        let outcomes: Vec<(i64, f64)> = round.outcomes().iter().map(
+            |&(units, p)| ((units * RESOLUTION).round() as i64, p)).collect();
+        // Every net the session can reach after each number of rounds
+        let mut reachable: Vec<Vec<i64>> = vec![vec![0]];
+        for n in range(0, max_rounds as usize) {
+            let mut next: Vec<i64> = Vec::new();
+            for x in reachable[n].iter() {
+                for &(o, _) in outcomes.iter() {
+                    next.push(*x + o);
+                }
+            }
+            next.sort();
+            next.dedup();
+            reachable.push(next);
+        }
+        let mut states: Vec<BTreeMap<i64, (f64, bool)>> = range(0, reachable.len()).map(
+            |_| BTreeMap::new()).collect();
+        for n in range(0, reachable.len()).rev() {
+            for x in reachable[n].iter() {
+                let quit_value = payoff.value(*x as f64 / RESOLUTION);
+                let state = if n == max_rounds as usize {
+                    (quit_value, true)
+                } else {
+                    let play_value = outcomes.iter().fold(0.0, |sum, &(o, p)| {
+                        sum + p * states[n + 1].get(&(*x + o)).unwrap().0
+                    });
+                    if quit_value >= play_value { (quit_value, true) } else { (play_value, false) }
+                };
+                states[n].insert(*x, state);
+            }
+        }
+        SessionPlan {
+            states: states,
+        }
+    }
+
+    // What the session is worth before the first round
+    pub fn value(&self) -> f64 {
+        self.states[0].get(&0).unwrap().0
+    }
+
+    // None if the net can't be reached in that many rounds
+    pub fn should_quit(&self, rounds_played: u32, net: f64) -> Option<bool> {
+        self.states.get(rounds_played as usize).and_then(
+            |s| s.get(&((net * RESOLUTION).round() as i64))).map(|s| s.1)
+    }
+
+    // The runs of reachable nets, lowest first, at which to quit
+    pub fn quit_ranges(&self, rounds_played: u32) -> Vec<(f64, f64)> {
+        let mut ret: Vec<(f64, f64)> = Vec::new();
+        let mut in_range = false;
+        match self.states.get(rounds_played as usize) {
+            Some(s) => for (x, state) in s.iter() {
+                let net = *x as f64 / RESOLUTION;
+                if state.1 {
+                    if in_range {
+                        ret.last_mut().unwrap().1 = net;
+                    } else {
+                        ret.push((net, net));
+                    }
+                }
+                in_range = state.1;
+            },
+            None => {}
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use payoff::{Payoff, SessionPlan};
+    use outcome::OutcomeDistribution;
+
+    #[test]
+    fn test_payoff_values() {
+        assert_eq!(-0.5, Payoff::LossRebate(0.5).value(-1.0));
+        assert_eq!(1.5, Payoff::LossRebate(0.1).value(1.5));
+        assert_eq!(0.0, Payoff::PromoChip(0.4).value(-1.0));
+        assert_eq!(-1.0, Payoff::PromoChip(0.4).value(-2.0));
+        assert_eq!(0.4, Payoff::PromoChip(0.4).value(0.0));
+        assert_eq!(2.0, Payoff::MatchPlay(0.4).value(1.0));
+        assert_eq!(1.0, Payoff::AtLeast(2.0).value(2.0));
+        assert_eq!(0.0, Payoff::AtLeast(2.0).value(1.5));
+        assert!("rebate:0.1".parse::<Payoff>().unwrap() == Payoff::LossRebate(0.1));
+        assert_eq!("match:0.45", format!("{:?}", "match:0.45".parse::<Payoff>().unwrap()));
+        assert!("rebate:2".parse::<Payoff>().is_err());
+        assert!("units:1".parse::<Payoff>().is_err());
+    }
+
+    #[test]
+    fn test_session_rebate() {
+        // A coin flip losing a little on average
+        let mut round = OutcomeDistribution::new();
+        round.add(1.0, 0.49);
+        round.add(-1.0, 0.51);
+        // Straight up it isn't worth playing at all
+        let plan = SessionPlan::new(&round, Payoff::Units, 10);
+        assert_eq!(0.0, plan.value());
+        assert_eq!(Some(true), plan.should_quit(0, 0.0));
+        // Half of a losing session back makes it worth a go
+        let plan = SessionPlan::new(&round, Payoff::LossRebate(0.5), 10);
+        assert!(plan.value() > 0.0);
+        assert_eq!(Some(false), plan.should_quit(0, 0.0));
+        assert_eq!(Some(false), plan.should_quit(5, -1.0));
+        assert_eq!(None, plan.should_quit(1, 0.0));
+        // Too far ahead or behind for the rebate to change sides, so there's
+        // nothing left but the house edge
+        assert_eq!(Some(true), plan.should_quit(5, 5.0));
+        assert_eq!(Some(true), plan.should_quit(5, -5.0));
+        let ranges = plan.quit_ranges(5);
+        assert_eq!(-5.0, ranges[0].0);
+        assert_eq!(5.0, ranges[ranges.len() - 1].1);
+        assert_eq!(vec![(-10.0, 10.0)], plan.quit_ranges(10));
+    }
+}
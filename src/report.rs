@@ -0,0 +1,187 @@
+use std::num::Float;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, PartialEq, Show)]
+pub enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Unknown format {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Show)]
+pub enum Cell {
+    Text(String),
+    Number(f64),
+}
+
+/**
+  A table of results rendered as aligned text, CSV or a JSON array of
+  objects keyed by the header.  Numbers get six decimal places.
+  */
+pub struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    pub fn new(header: &[&str]) -> Table {
+        Table {
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn add_row(&mut self, row: Vec<Cell>) {
+        assert_eq!(self.header.len(), row.len());
+        self.rows.push(row);
+    }
+
+    pub fn header(&self) -> &Vec<String> {
+        &self.header
+    }
+
+    pub fn rows(&self) -> &Vec<Vec<Cell>> {
+        &self.rows
+    }
+
+    pub fn render(&self, format: &Format) -> String {
+        let cells: Vec<Vec<String>> = self.rows.iter().map(|r| r.iter().map(|c| match *c {
+            Cell::Text(ref s) => s.clone(),
+            Cell::Number(n) => format!("{:.6}", n),
+        }).collect()).collect();
+        let mut s = String::new();
+        match *format {
+            Format::Csv => {
+                let header: Vec<String> = self.header.iter().map(|h| csv_field(h.as_slice())).collect();
+                s.push_str(header.connect(",").as_slice());
+                s.push('\n');
+                for r in cells.iter() {
+                    let fields: Vec<String> = r.iter().map(|c| csv_field(c.as_slice())).collect();
+                    s.push_str(fields.connect(",").as_slice());
+                    s.push('\n');
+                }
+            }
+            Format::Json => {
+                let rows: Vec<String> = self.rows.iter().zip(cells.iter()).map(|(row, text)| {
+                    let fields: Vec<String> = range(0, row.len()).map(|i| {
+                        let value = match row[i] {
+                            Cell::Text(_) => json_string(text[i].as_slice()),
+                            // JSON has no NaN or infinity
+                            Cell::Number(n) if !n.is_finite() => "null".to_string(),
+                            Cell::Number(_) => text[i].clone(),
+                        };
+                        format!("{}:{}", json_string(self.header[i].as_slice()), value)
+                    }).collect();
+                    format!("{{{}}}", fields.connect(","))
+                }).collect();
+                s.push_str(format!("[{}]\n", rows.connect(",\n ")).as_slice());
+            }
+            Format::Text => {
+                let mut widths: Vec<usize> = self.header.iter().map(|h| h.len()).collect();
+                for r in cells.iter() {
+                    for (i, c) in r.iter().enumerate() {
+                        if c.len() > widths[i] {
+                            widths[i] = c.len();
+                        }
+                    }
+                }
+                let mut lines = vec![self.header.clone()];
+                lines.push_all(cells.as_slice());
+                for r in lines.iter() {
+                    let padded: Vec<String> = r.iter().enumerate().map(
+                        |(i, c)| format!("{:1$}", c, widths[i])).collect();
+                    s.push_str(padded.connect("  ").as_slice().trim_right());
+                    s.push('\n');
+                }
+            }
+        }
+        s
+    }
+}
+
+// Quoted, with quotes doubled, when it holds a comma, quote or line break
+fn csv_field(s: &str) -> String {
+    if !s.chars().any(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        return s.to_string();
+    }
+    let mut ret = String::from_str("\"");
+    for c in s.chars() {
+        if c == '"' {
+            ret.push('"');
+        }
+        ret.push(c);
+    }
+    ret.push('"');
+    ret
+}
+
+fn json_string(s: &str) -> String {
+    let mut ret = String::from_str("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            '\r' => ret.push_str("\\r"),
+            '\t' => ret.push_str("\\t"),
+            c if (c as u32) < 0x20 => ret.push_str(format!("\\u{:04x}", c as u32).as_slice()),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use report::{Table, Cell, Format};
+    use std::num::Float;
+
+    fn table() -> Table {
+        let mut t = Table::new(&["name", "ev"]);
+        t.add_row(vec![Cell::Text("a, \"b\"".to_string()), Cell::Number(0.5)]);
+        t.add_row(vec![Cell::Text("back\\slash\nline".to_string()), Cell::Number(-1.0)]);
+        t
+    }
+
+    #[test]
+    fn test_text() {
+        let mut t = Table::new(&["action", "ev"]);
+        t.add_row(vec![Cell::Text("STAND".to_string()), Cell::Number(-0.25)]);
+        assert_eq!("action  ev\nSTAND   -0.250000\n", t.render(&Format::Text));
+    }
+
+    #[test]
+    fn test_csv_escaping() {
+        assert_eq!("name,ev\n\"a, \"\"b\"\"\",0.500000\n\"back\\slash\nline\",-1.000000\n",
+                   table().render(&Format::Csv));
+    }
+
+    #[test]
+    fn test_json_escaping() {
+        let mut t = table();
+        t.add_row(vec![Cell::Text("\t".to_string()), Cell::Number(Float::nan())]);
+        assert_eq!("[{\"name\":\"a, \\\"b\\\"\",\"ev\":0.500000},\n \
+                    {\"name\":\"back\\\\slash\\nline\",\"ev\":-1.000000},\n \
+                    {\"name\":\"\\t\",\"ev\":null}]\n",
+                   t.render(&Format::Json));
+    }
+
+    #[test]
+    fn test_format() {
+        assert!("csv".parse::<Format>() == Ok(Format::Csv));
+        assert!("xml".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,508 @@
+use std::fmt;
+use bjaction::BJAction;
+use bjaction::BJAction::{HIT, STAND, DOUBLE, SPLIT, SURRENDER};
+use bjaction::BJ_ACTIONS;
+use cards::card::Card;
+use cards::value::ACE;
+use cards::value::VALUES;
+use hand::BJHand;
+use hand::score_for_value;
+use rules::BJRules;
+use shoe::shoe::DirectShoe;
+use shoe::composition::ShoeComposition;
+
+#[derive(Copy, Clone, PartialEq, Show)]
+pub enum RoundState {
+    // The dealer shows an ace and is waiting on take_insurance() from each
+    // seat in turn
+    Insurance,
+    PlayerTurn,
+    Finished,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum RoundError {
+    // The round isn't at a point where that can happen
+    WrongState(RoundState),
+    IllegalAction(BJAction),
+    SplitLimitReached,
+    OutOfCards,
+}
+
+impl fmt::Show for RoundError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RoundError::WrongState(s) => write!(f, "not allowed while {:?}", s),
+            RoundError::IllegalAction(a) => write!(f, "{:?} is not allowed on this hand", a),
+            RoundError::SplitLimitReached => write!(f, "no more splits allowed for this seat"),
+            RoundError::OutOfCards => write!(f, "the shoe ran out of cards"),
+        }
+    }
+}
+
+// One hand of a seat's, with the money riding on it
+pub struct PlayerHand {
+    seat: usize,
+    hand: BJHand,
+    wager: f64,
+    surrendered: bool,
+}
+
+impl PlayerHand {
+    pub fn seat(&self) -> usize {
+        self.seat
+    }
+
+    pub fn hand(&self) -> &BJHand {
+        &self.hand
+    }
+
+    pub fn wager(&self) -> f64 {
+        self.wager
+    }
+
+    pub fn surrendered(&self) -> bool {
+        self.surrendered
+    }
+
+    pub fn is_bust(&self) -> bool {
+        self.hand.score() > 21
+    }
+}
+
+/**
+  A single round of blackjack between the dealer and one or more seats,
+  which play their hands in seat order.  deal() starts the round and each
+  call moves it along; anything the rules don't allow comes back as an error
+  and leaves the round as it was.  Running out of cards is different: the
+  move has already been made when a later card is missing, so OutOfCards
+  leaves the round part way through and it shouldn't be played on.  The round
+  never calls end_round() on the shoe, since other rounds may share it.
+  */
+pub struct Round<'a> {
+    rules: BJRules,
+    shoe: &'a mut (DirectShoe + 'a),
+    dealer: BJHand,
+    hands: Vec<PlayerHand>,
+    active: usize,
+    // Per seat
+    splits: Vec<u32>,
+    insurance: Vec<f64>,
+    // The seat deciding on insurance
+    insurance_seat: usize,
+    state: RoundState,
+}
+
+impl <'a>Round<'a> {
+    pub fn deal(rules: BJRules, shoe: &'a mut (DirectShoe + 'a),
+                wager: f64) -> Result<Round<'a>, RoundError> {
+        Round::deal_seats(rules, shoe, &[wager])
+    }
+
+    // A card to each seat and then the dealer, twice
+    pub fn deal_seats(rules: BJRules, shoe: &'a mut (DirectShoe + 'a),
+                      wagers: &[f64]) -> Result<Round<'a>, RoundError> {
+        assert!(wagers.len() > 0);
+        let mut players: Vec<BJHand> = wagers.iter().map(|_| BJHand::new()).collect();
+        let mut dealer = BJHand::new();
+        for _ in range(0, 2) {
+            for hand in players.iter_mut().chain(Some(&mut dealer).into_iter()) {
+                match shoe.pop() {
+                    Some(c) => {
+                        hand.add_card(&c);
+                    }
+                    None => return Err(RoundError::OutOfCards),
+                }
+            }
+        }
+        let mut ret = Round {
+            rules: rules,
+            shoe: shoe,
+            dealer: dealer,
+            hands: players.into_iter().zip(wagers.iter()).enumerate().map(
+                |(seat, (hand, wager))| PlayerHand {
+                    seat: seat,
+                    hand: hand,
+                    wager: *wager,
+                    surrendered: false,
+                }).collect(),
+            active: 0,
+            splits: wagers.iter().map(|_| 0).collect(),
+            insurance: wagers.iter().map(|_| 0.0).collect(),
+            insurance_seat: 0,
+            state: RoundState::PlayerTurn,
+        };
+        if ret.dealer_up_card().value() == &ACE && !rules.dealer_blackjack_after_hand() &&
+            !rules.double_exposure() {
+            ret.state = RoundState::Insurance;
+        } else {
+            try!(ret.peek());
+        }
+        Ok(ret)
+    }
+
+    pub fn state(&self) -> RoundState {
+        self.state
+    }
+
+    pub fn rules(&self) -> &BJRules {
+        &self.rules
+    }
+
+    pub fn dealer_up_card(&self) -> &Card {
+        &self.dealer.cards()[0]
+    }
+
+    // The dealer's whole hand, once the hole card is turned over.  Double
+    // Exposure shows it from the start
+    pub fn dealer_hand(&self) -> Option<&BJHand> {
+        match self.state {
+            RoundState::Finished => Some(&self.dealer),
+            _ if self.rules.double_exposure() => Some(&self.dealer),
+            _ => None,
+        }
+    }
+
+    // Every seat's hands, in the order they're played
+    pub fn hands(&self) -> &Vec<PlayerHand> {
+        &self.hands
+    }
+
+    pub fn seats(&self) -> usize {
+        self.splits.len()
+    }
+
+    pub fn active_seat(&self) -> Option<usize> {
+        self.active_hand().map(|h| h.seat)
+    }
+
+    pub fn insurance_seat(&self) -> Option<usize> {
+        match self.state {
+            RoundState::Insurance => Some(self.insurance_seat),
+            _ => None,
+        }
+    }
+
+    pub fn active_hand_index(&self) -> Option<usize> {
+        match self.state {
+            RoundState::PlayerTurn => Some(self.active),
+            _ => None,
+        }
+    }
+
+    pub fn active_hand(&self) -> Option<&PlayerHand> {
+        self.active_hand_index().map(|i| &self.hands[i])
+    }
+
+    // The shoe plus the dealer's hole card until it's turned over
+    pub fn unseen_composition(&self) -> ShoeComposition {
+        let mut ret = ShoeComposition::new_empty();
+        for v in VALUES.iter() {
+            ret.add(v, self.shoe.count(v));
+        }
+        if self.state != RoundState::Finished && !self.rules.double_exposure() {
+            ret.add(self.dealer.cards()[1].value(), 1);
+        }
+        ret
+    }
+
+    // Insuring the seat's hand: half its wager at 2:1 on the hole card being a ten
+    pub fn insurance_ev(&self, seat: usize) -> f64 {
+        let p = self.unseen_composition().ten_density();
+        self.hands[seat].wager / 2.0 * (3.0 * p - 1.0)
+    }
+
+    // All the seats' insurance
+    pub fn insurance(&self) -> f64 {
+        self.insurance.iter().fold(0.0, |sum, i| sum + *i)
+    }
+
+    pub fn seat_insurance(&self, seat: usize) -> f64 {
+        self.insurance[seat]
+    }
+
+    // For insurance_seat(), and the dealer peeks once every seat has decided
+    pub fn take_insurance(&mut self, take: bool) -> Result<(), RoundError> {
+        if self.state != RoundState::Insurance {
+            return Err(RoundError::WrongState(self.state));
+        }
+        let seat = self.insurance_seat;
+        if take {
+            self.insurance[seat] = self.hands[seat].wager / 2.0;
+        }
+        self.insurance_seat += 1;
+        if self.insurance_seat < self.seats() {
+            return Ok(());
+        }
+        self.state = RoundState::PlayerTurn;
+        self.peek()
+    }
+
+    // What the active hand may do right now
+    pub fn legal_actions(&self) -> Vec<BJAction> {
+        match self.active_hand() {
+            None => Vec::new(),
+            Some(h) => BJ_ACTIONS.iter().filter(|a| {
+                self.rules.can_take_action(&h.hand, **a) &&
+                    (**a != SPLIT || self.splits[h.seat] < self.rules.split_limit())
+            }).map(|a| *a).collect(),
+        }
+    }
+
+    pub fn act(&mut self, action: BJAction) -> Result<(), RoundError> {
+        if self.state != RoundState::PlayerTurn {
+            return Err(RoundError::WrongState(self.state));
+        }
+        if !self.rules.can_take_action(&self.hands[self.active].hand, action) {
+            return Err(RoundError::IllegalAction(action));
+        }
+        let active = self.active;
+        let seat = self.hands[active].seat;
+        if action == SPLIT && self.splits[seat] >= self.rules.split_limit() {
+            return Err(RoundError::SplitLimitReached);
+        }
+        if (action == HIT || action == DOUBLE || action == SPLIT) && self.shoe.len() == 0 {
+            return Err(RoundError::OutOfCards);
+        }
+        match action {
+            HIT => {
+                let c = self.shoe.pop().unwrap();
+                self.hands[active].hand.add_card(&c);
+            }
+            STAND => {
+                self.active += 1;
+            }
+            DOUBLE => {
+                let c = self.shoe.pop().unwrap();
+                let factor = self.rules.double_bet_factor(&self.hands[active].hand);
+                let ref mut h = self.hands[active];
+                h.wager *= factor;
+                h.hand.add_double_count();
+                h.hand.add_card(&c);
+                // A Pontoon hand plays on after buying a card
+                if !self.rules.pontoon() {
+                    self.active += 1;
+                }
+            }
+            SPLIT => {
+                let split_hand = {
+                    let ref mut h = self.hands[active];
+                    h.hand.split();
+                    h.hand.take_split_hand()
+                };
+                let wager = self.hands[active].wager;
+                self.hands.insert(active + 1, PlayerHand {
+                    seat: seat,
+                    hand: split_hand,
+                    wager: wager,
+                    surrendered: false,
+                });
+                self.splits[seat] += 1;
+                let c = self.shoe.pop().unwrap();
+                self.hands[active].hand.add_card(&c);
+            }
+            SURRENDER => {
+                self.hands[active].surrendered = true;
+                self.active += 1;
+            }
+        }
+        self.advance()
+    }
+
+    // Net units won or lost by every seat, insurance included, once the
+    // round is over
+    pub fn net_result(&self) -> Option<f64> {
+        if self.state != RoundState::Finished {
+            return None;
+        }
+        Some(range(0, self.seats()).fold(0.0, |sum, seat| sum + self.seat_result(seat).unwrap()))
+    }
+
+    pub fn seat_result(&self, seat: usize) -> Option<f64> {
+        if self.state != RoundState::Finished {
+            return None;
+        }
+        let dealer_bj = self.rules.is_blackjack(&self.dealer);
+        let insurance = self.insurance[seat];
+        let mut ret = if dealer_bj { 2.0 * insurance } else { -insurance };
+        for h in self.hands.iter().filter(|h| h.seat == seat) {
+            ret += self.hand_result(h, dealer_bj);
+        }
+        Some(ret)
+    }
+
+    fn hand_result(&self, h: &PlayerHand, dealer_bj: bool) -> f64 {
+        let player_bj = self.rules.is_blackjack(&h.hand);
+        if h.surrendered {
+            -h.wager / 2.0
+        } else if h.is_bust() {
+            -h.wager
+        } else if dealer_bj {
+            if player_bj { self.rules.blackjack_tie_result() * h.wager } else { -h.wager }
+        } else if player_bj {
+            self.rules.blackjack_payout() * h.wager
+        } else if self.rules.is_automatic_win(&h.hand) {
+            self.rules.five_card_trick_payout() * h.wager
+        } else if self.dealer.score() > 21 || self.dealer.score() < h.hand.score() {
+            h.wager
+        } else if self.dealer.score() > h.hand.score() || self.rules.dealer_wins_ties() {
+            -h.wager
+        } else {
+            0.0
+        }
+    }
+
+    // Ends the round right away on a dealer blackjack, or when every seat
+    // has one
+    fn peek(&mut self) -> Result<(), RoundError> {
+        let up_score = score_for_value(self.dealer_up_card().value());
+        let can_peek = !self.rules.dealer_blackjack_after_hand() && (up_score == 1 || up_score == 10);
+        if (can_peek && self.rules.is_blackjack(&self.dealer)) ||
+            self.hands.iter().all(|h| self.rules.is_blackjack(&h.hand)) {
+            self.state = RoundState::Finished;
+            return Ok(());
+        }
+        self.advance()
+    }
+
+    /**
+      Deals the second card to split hands and skips every hand with nothing
+      left to decide.  The dealer plays once no hands are left.
+      */
+    fn advance(&mut self) -> Result<(), RoundError> {
+        while self.active < self.hands.len() {
+            if self.hands[self.active].hand.len() == 1 {
+                match self.shoe.pop() {
+                    Some(c) => {
+                        self.hands[self.active].hand.add_card(&c);
+                    }
+                    None => return Err(RoundError::OutOfCards),
+                }
+            }
+            let done = {
+                let ref h = self.hands[self.active];
+                h.hand.score() >= 21 || BJ_ACTIONS.iter().all(
+                    |a| *a == STAND || !self.rules.can_take_action(&h.hand, *a) ||
+                        (*a == SPLIT && self.splits[h.seat] >= self.rules.split_limit()))
+            };
+            if !done {
+                return Ok(());
+            }
+            self.active += 1;
+        }
+        self.play_dealer()
+    }
+
+    fn play_dealer(&mut self) -> Result<(), RoundError> {
+        let anything_to_beat = self.hands.iter().any(
+            |h| !h.surrendered && !h.is_bust() && !self.rules.is_blackjack(&h.hand));
+        while anything_to_beat && self.rules.should_hit_dealer_hand(&self.dealer) {
+            match self.shoe.pop() {
+                Some(c) => {
+                    self.dealer.add_card(&c);
+                }
+                None => return Err(RoundError::OutOfCards),
+            }
+        }
+        self.state = RoundState::Finished;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use round::{Round, RoundState, RoundError};
+    use rules::BJRules;
+    use bjaction::BJAction::{HIT, STAND, DOUBLE, SPLIT, SURRENDER};
+    use cards::card::Card;
+    use cards::suit::SPADE;
+    use cards::value::Value;
+    use cards::value::{ACE, TWO, THREE, SIX, SEVEN, EIGHT, NINE, TEN, KING};
+    use shoe::directshoe::DirectActualShoe;
+
+    // A shoe of just these cards, which come out in the order given
+    pub fn stacked_shoe<'a>(cards: &'a mut Vec<Card>, values: &[Value]) -> DirectActualShoe<'a> {
+        *cards = values.iter().rev().map(|v| Card::new(*v, SPADE)).collect();
+        DirectActualShoe {
+            initial_length: Some(values.len() as u32),
+            maximum_count_of_any_value: Some(4),
+            cards: cards,
+        }
+    }
+
+    #[test]
+    fn test_stand_and_win() {
+        let mut cards = Vec::new();
+        let mut shoe = stacked_shoe(&mut cards, &[TEN, NINE, NINE, EIGHT]);
+        let mut round = Round::deal(BJRules::new(), &mut shoe, 1.0).unwrap();
+        assert_eq!(RoundState::PlayerTurn, round.state());
+        assert!(round.dealer_hand().is_none());
+        assert!(round.act(SURRENDER) == Err(RoundError::IllegalAction(SURRENDER)));
+        assert!(round.act(STAND).is_ok());
+        assert_eq!(RoundState::Finished, round.state());
+        assert_eq!(Some(1.0), round.net_result());
+        assert!(round.act(STAND) == Err(RoundError::WrongState(RoundState::Finished)));
+    }
+
+    #[test]
+    fn test_split_and_double() {
+        let mut cards = Vec::new();
+        let mut shoe = stacked_shoe(&mut cards, &[EIGHT, SIX, EIGHT, TEN, THREE, TEN, NINE, TEN]);
+        let mut round = Round::deal(BJRules::new(), &mut shoe, 1.0).unwrap();
+        assert!(round.legal_actions().contains(&SPLIT));
+        round.act(SPLIT).unwrap();
+        assert_eq!(2, round.hands().len());
+        assert_eq!(11, round.active_hand().unwrap().hand().score());
+        round.act(DOUBLE).unwrap();
+        assert_eq!(Some(1), round.active_hand_index());
+        assert_eq!(17, round.active_hand().unwrap().hand().score());
+        round.act(STAND).unwrap();
+        assert_eq!(26, round.dealer_hand().unwrap().score());
+        assert_eq!(2.0, round.hands()[0].wager());
+        assert_eq!(Some(3.0), round.net_result());
+    }
+
+    #[test]
+    fn test_insurance_against_blackjack() {
+        let mut cards = Vec::new();
+        let mut shoe = stacked_shoe(&mut cards, &[TEN, ACE, NINE, KING]);
+        let mut round = Round::deal(BJRules::new(), &mut shoe, 2.0).unwrap();
+        assert_eq!(RoundState::Insurance, round.state());
+        assert!(round.act(STAND) == Err(RoundError::WrongState(RoundState::Insurance)));
+        // Only the king is unseen
+        assert_eq!(2.0, round.insurance_ev(0));
+        round.take_insurance(true).unwrap();
+        assert_eq!(RoundState::Finished, round.state());
+        assert_eq!(Some(0.0), round.net_result());
+    }
+
+    #[test]
+    fn test_double_exposure_tie() {
+        let mut cards = Vec::new();
+        let mut shoe = stacked_shoe(&mut cards, &[TEN, TEN, KING, KING]);
+        let mut rules = BJRules::new();
+        rules.set_double_exposure(true, false);
+        let mut round = Round::deal(rules, &mut shoe, 1.0).unwrap();
+        assert_eq!(20, round.dealer_hand().unwrap().score());
+        round.act(STAND).unwrap();
+        assert_eq!(Some(-1.0), round.net_result());
+    }
+
+    #[test]
+    fn test_pontoon_buy_and_trick() {
+        let mut cards = Vec::new();
+        let mut shoe = stacked_shoe(&mut cards, &[TWO, TEN, THREE, SEVEN, TWO, THREE, TWO]);
+        let mut rules = BJRules::new();
+        rules.set_pontoon(true);
+        let mut round = Round::deal(rules, &mut shoe, 1.0).unwrap();
+        // 5 has to twist or buy
+        assert!(!round.legal_actions().contains(&STAND));
+        round.act(DOUBLE).unwrap();
+        round.act(DOUBLE).unwrap();
+        assert_eq!(3.0, round.hands()[0].wager());
+        round.act(HIT).unwrap();
+        // Five cards for 12 beat the dealer's 17 at 2:1
+        assert_eq!(RoundState::Finished, round.state());
+        assert_eq!(Some(6.0), round.net_result());
+    }
+}
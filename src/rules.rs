@@ -1,14 +1,18 @@
 use hand::BJHand;
 use std::fmt;
+use std::num::Float;
+use std::str::FromStr;
+use std::ascii::AsciiExt;
 use bjaction::BJAction;
 use bjaction::BJAction::HIT;
 use cards::value::ACE;
 use bjaction::BJAction::STAND;
 use bjaction::BJAction::DOUBLE;
 use bjaction::BJAction::SURRENDER;
+use shoe::composition::ShoeComposition;
 use bjaction::BJAction::SPLIT;
 
-#[derive(Copy)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct BJRules{
     can_surrender: bool,
     split_limit: u32,
@@ -17,8 +21,20 @@ pub struct BJRules{
     resplit_aces: bool,
     draw_on_split_aces: bool,
     double_after_split: bool,
+    blackjack_payout: f64,
+    // Both dealer cards dealt face up, and the dealer wins ties
+    double_exposure: bool,
+    player_blackjack_wins_ties: bool,
+    // British Pontoon: five card tricks, dealer wins ties, buying cards
+    pontoon: bool,
+    // Spanish decks, and the bet can be doubled after seeing the up card
+    double_attack: bool,
 }
 
+// Pontoon players have to twist below this
+const PONTOON_MIN_STAND: u32 = 15;
+const FIVE_CARD_TRICK: u32 = 5;
+
 impl BJRules {
     /**
       Default rules: noR, split-4, s17, Dany, no RsA, Single card on ace splits, DaS
@@ -39,10 +55,18 @@ impl BJRules {
             resplit_aces: resplit_aces,
             draw_on_split_aces: draw_on_split_aces,
             double_after_split: double_after_split,
+            blackjack_payout: 1.5,
+            double_exposure: false,
+            player_blackjack_wins_ties: false,
+            pontoon: false,
+            double_attack: false,
         }
     }
 
     pub fn can_double(&self, h: &BJHand) -> bool {
+        if self.pontoon {
+            return self.can_buy(h);
+        }
         if h.split_number() > 0 {
             if !self.double_after_split {
                 return false;
@@ -57,6 +81,33 @@ impl BJRules {
             h.double_count() < self.max_doubles_single_hand
     }
 
+    // Pontoon buys a card for another stake on two to four cards, until the
+    // player twists
+    fn can_buy(&self, h: &BJHand) -> bool {
+        if h.split_number() > 0 && !self.double_after_split {
+            return false;
+        }
+        let twists = h.len() as i32 - 2 - h.double_count() as i32;
+        h.len() >= 2 &&
+            h.len() < FIVE_CARD_TRICK &&
+            h.score() < 21 &&
+            twists <= 0 &&
+            h.double_count() < self.max_doubles_single_hand
+    }
+
+    /**
+      How much a double multiplies the hand's bet by.  Blackjack doubles it;
+      each Pontoon buy adds the original stake again.
+      */
+    pub fn double_bet_factor(&self, h: &BJHand) -> f64 {
+        if self.pontoon {
+            let stakes = h.double_count() as f64 + 1.0;
+            (stakes + 1.0) / stakes
+        } else {
+            2.0
+        }
+    }
+
     pub fn max_doubles_single_hand(&self) -> u32 {
         self.max_doubles_single_hand
     }
@@ -72,7 +123,8 @@ impl BJRules {
     }
 
     pub fn can_stand(&self, h: &BJHand) -> bool {
-        h.len() > 1
+        h.len() > 1 &&
+            (!self.pontoon || h.score() >= PONTOON_MIN_STAND || !self.can_hit(h))
     }
 
     pub fn can_split(&self, h: &BJHand) -> bool {
@@ -110,11 +162,102 @@ impl BJRules {
     }
 
     pub fn blackjack_payout(&self) -> f64 {
-        1.5
+        self.blackjack_payout
+    }
+
+    pub fn set_blackjack_payout(&mut self, payout: f64) -> &mut BJRules {
+        self.blackjack_payout = payout;
+        self
+    }
+
+    /**
+      Double Exposure: the player sees both dealer cards, the dealer wins
+      ties and blackjack pays even money.  A player blackjack beats a dealer
+      one if player_blackjack_wins_ties, and loses to it otherwise.
+      */
+    pub fn set_double_exposure(&mut self, double_exposure: bool,
+                               player_blackjack_wins_ties: bool) -> &mut BJRules {
+        self.double_exposure = double_exposure;
+        self.player_blackjack_wins_ties = double_exposure && player_blackjack_wins_ties;
+        if double_exposure {
+            self.blackjack_payout = 1.0;
+        }
+        self
+    }
+
+    pub fn double_exposure(&self) -> bool {
+        self.double_exposure
+    }
+
+    pub fn dealer_wins_ties(&self) -> bool {
+        self.double_exposure || self.pontoon
+    }
+
+    pub fn player_blackjack_wins_ties(&self) -> bool {
+        self.player_blackjack_wins_ties
+    }
+
+    // What a player blackjack wins against a dealer blackjack
+    pub fn blackjack_tie_result(&self) -> f64 {
+        if self.player_blackjack_wins_ties {
+            self.blackjack_payout
+        } else if self.dealer_wins_ties() {
+            -1.0
+        } else {
+            0.0
+        }
+    }
+
+    /**
+      British Pontoon: pontoon pays 2:1, five cards without busting are a
+      trick paying 2:1, the dealer wins ties, the player has to twist below
+      15, and up to three cards can be bought for another stake each, on
+      hands of two to four cards.
+      */
+    pub fn set_pontoon(&mut self, pontoon: bool) -> &mut BJRules {
+        self.pontoon = pontoon;
+        if pontoon {
+            self.blackjack_payout = 2.0;
+            self.max_doubles_single_hand = 3;
+            self.can_surrender = false;
+        }
+        self
+    }
+
+    pub fn pontoon(&self) -> bool {
+        self.pontoon
+    }
+
+    /**
+      Double Attack Blackjack: dealt from Spanish decks with no tens,
+      blackjack pays even money, late surrender is allowed and the player
+      can double the bet after seeing the dealer's up card.
+      */
+    pub fn set_double_attack(&mut self, double_attack: bool) -> &mut BJRules {
+        self.double_attack = double_attack;
+        if double_attack {
+            self.blackjack_payout = 1.0;
+            self.can_surrender = true;
+        }
+        self
+    }
+
+    pub fn double_attack(&self) -> bool {
+        self.double_attack
+    }
+
+    pub fn five_card_trick_payout(&self) -> f64 {
+        2.0
+    }
+
+    // Whether the hand is a five card trick
+    pub fn is_automatic_win(&self, h: &BJHand) -> bool {
+        let length = self.automatic_win_at_hand_length();
+        length > 0 && h.len() >= length && h.score() <= 21
     }
 
     pub fn automatic_win_at_hand_length(&self) -> u32 {
-        0
+        if self.pontoon { FIVE_CARD_TRICK } else { 0 }
     }
 
     pub fn can_hit(&self, h: &BJHand) -> bool {
@@ -125,6 +268,10 @@ impl BJRules {
                 return h.len() == 1
             }
         }
+        if self.pontoon {
+            // Twisting is allowed after buying, up to the five card trick
+            return h.score() < 21 && h.len() < FIVE_CARD_TRICK;
+        }
         h.score() < 21 && h.double_count() == 0
     }
 
@@ -139,13 +286,218 @@ impl BJRules {
     }
 }
 
+/**
+  The canonical notation, like `S17 DAS RSA LS SPL3 DBL1 BJ3:2`.  Soft 17 is
+  always given as H17 or S17.  Other flags that are off are left out, except
+  NDAS since doubling after splits is the default.  A payout that isn't a
+  ratio of small numbers is written as a decimal, like BJ1.2345.  Double
+  Exposure ends it with DE, or DEBJ when a player blackjack wins ties,
+  Pontoon with PON and Double Attack with DA.
+  */
 impl fmt::Show for BJRules {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "sur={};splits={}",
-            self.can_surrender,
-            self.split_limit)
+        let mut tokens = vec![String::from_str(if self.hit_s17 { "H17" } else { "S17" })];
+        tokens.push(String::from_str(if self.double_after_split { "DAS" } else { "NDAS" }));
+        if self.resplit_aces {
+            tokens.push(String::from_str("RSA"));
+        }
+        if self.draw_on_split_aces {
+            tokens.push(String::from_str("HSA"));
+        }
+        if self.can_surrender {
+            tokens.push(String::from_str("LS"));
+        }
+        tokens.push(format!("SPL{}", self.split_limit));
+        tokens.push(format!("DBL{}", self.max_doubles_single_hand));
+        tokens.push(format!("BJ{}", payout_ratio(self.blackjack_payout)));
+        if self.double_exposure {
+            tokens.push(String::from_str(if self.player_blackjack_wins_ties { "DEBJ" } else { "DE" }));
+        }
+        if self.pontoon {
+            tokens.push(String::from_str("PON"));
+        }
+        if self.double_attack {
+            tokens.push(String::from_str("DA"));
+        }
+        write!(f, "{}", tokens.connect(" "))
+    }
+}
+
+// 1.5 as 3:2, 1.2 as 6:5 ... and anything else as a decimal that parses
+// back to the same payout
+fn payout_ratio(payout: f64) -> String {
+    for to in range(1, 101) {
+        let paid = payout * to as f64;
+        let rounded = (paid + 0.5) as u32;
+        if (paid - rounded as f64) < 1e-9 && (rounded as f64 - paid) < 1e-9 {
+            return format!("{}:{}", rounded, to);
+        }
+    }
+    let short = format!("{}", payout);
+    if short.as_slice().contains(".") && short.parse::<f64>().ok() == Some(payout) {
+        short
+    } else {
+        format!("{:.17}", payout)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct RulesParseError {
+    pub token: String,
+    pub reason: &'static str,
+}
+
+impl fmt::Show for RulesParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "bad rules token '{}': {}", self.token, self.reason)
+    }
+}
+
+/**
+  Rules plus the number of decks they're dealt from, the way rule sets are
+  usually written down: `6D H17 DAS RSA LS SPL3 BJ3:2`.  Tokens are case
+  insensitive and can come in any order.  Anything left out keeps the value
+  from BJRules::new(), and the decks are optional.
+  */
+#[derive(Copy, Clone, PartialEq)]
+pub struct RuleSet {
+    pub decks: Option<u32>,
+    pub rules: BJRules,
+}
+
+impl RuleSet {
+    pub fn parse(s: &str) -> Result<RuleSet, RulesParseError> {
+        let mut ret = RuleSet {
+            decks: None,
+            rules: BJRules::new(),
+        };
+        // Each of these can only be set once
+        let mut seen: Vec<&'static str> = Vec::new();
+        for token in s.split(|c: char| c.is_whitespace() || c == ',').filter(|t| t.len() > 0) {
+            let upper = token.to_ascii_uppercase();
+            let t = upper.as_slice();
+            let error = |reason| RulesParseError {
+                token: String::from_str(token),
+                reason: reason,
+            };
+            let setting = if t == "H17" || t == "S17" {
+                ret.rules.hit_s17 = t == "H17";
+                "soft 17"
+            } else if t == "DAS" || t == "NDAS" {
+                ret.rules.double_after_split = t == "DAS";
+                "double after split"
+            } else if t == "RSA" || t == "NRSA" {
+                ret.rules.resplit_aces = t == "RSA";
+                "resplit aces"
+            } else if t == "HSA" || t == "NHSA" {
+                ret.rules.draw_on_split_aces = t == "HSA";
+                "hit split aces"
+            } else if t == "LS" || t == "NS" {
+                ret.rules.can_surrender = t == "LS";
+                "surrender"
+            } else if t == "DE" || t == "DEBJ" {
+                ret.rules.double_exposure = true;
+                ret.rules.player_blackjack_wins_ties = t == "DEBJ";
+                "double exposure"
+            } else if t == "PON" {
+                ret.rules.pontoon = true;
+                "pontoon"
+            } else if t == "DA" {
+                ret.rules.double_attack = true;
+                "double attack"
+            } else if t.starts_with("SPL") {
+                ret.rules.split_limit = try!(t[3..].parse::<u32>().map_err(
+                    |_| error("SPL needs the number of splits allowed")));
+                "splits"
+            } else if t.starts_with("DBL") {
+                ret.rules.max_doubles_single_hand = try!(t[3..].parse::<u32>().map_err(
+                    |_| error("DBL needs the number of doubles allowed on a hand")));
+                "doubles"
+            } else if t.starts_with("BJ") && t.contains(".") {
+                // A decimal needs the point, so BJ3 isn't taken for 3:1
+                ret.rules.blackjack_payout = try!(t[2..].parse::<f64>().ok().and_then(
+                    |p| if p >= 0.0 && p.is_finite() { Some(p) } else { None }).ok_or(
+                    error("BJ needs a payout like 3:2, 6:5 or 1.25")));
+                "blackjack payout"
+            } else if t.starts_with("BJ") {
+                let parts: Vec<&str> = t[2..].split(':').collect();
+                let ratio: Vec<u32> = parts.iter().filter_map(|p| p.parse::<u32>().ok()).collect();
+                if parts.len() != 2 || ratio.len() != 2 || ratio[1] == 0 {
+                    return Err(error("BJ needs a payout like 3:2 or 6:5"));
+                }
+                ret.rules.blackjack_payout = ratio[0] as f64 / ratio[1] as f64;
+                "blackjack payout"
+            } else if t.ends_with("D") && t.len() > 1 {
+                let decks = try!(t[..t.len() - 1].parse::<u32>().map_err(
+                    |_| error("unknown rule")));
+                if decks == 0 {
+                    return Err(error("need at least one deck"));
+                }
+                ret.decks = Some(decks);
+                "decks"
+            } else {
+                return Err(error("unknown rule"));
+            };
+            if seen.contains(&setting) {
+                return Err(error("sets a rule an earlier token already set"));
+            }
+            seen.push(setting);
+        }
+        // Double Exposure pays even money unless told otherwise
+        if ret.rules.double_exposure && !seen.contains(&"blackjack payout") {
+            ret.rules.blackjack_payout = 1.0;
+        }
+        // And Pontoon pays 2:1 and allows three buys
+        if ret.rules.pontoon {
+            if !seen.contains(&"blackjack payout") {
+                ret.rules.blackjack_payout = 2.0;
+            }
+            if !seen.contains(&"doubles") {
+                ret.rules.max_doubles_single_hand = 3;
+            }
+            if seen.contains(&"surrender") && ret.rules.can_surrender {
+                return Err(RulesParseError {
+                    token: String::from_str("LS"),
+                    reason: "Pontoon has no surrender",
+                });
+            }
+        }
+        // Double Attack pays even money with surrender unless told otherwise
+        if ret.rules.double_attack {
+            if !seen.contains(&"blackjack payout") {
+                ret.rules.blackjack_payout = 1.0;
+            }
+            if !seen.contains(&"surrender") {
+                ret.rules.can_surrender = true;
+            }
+        }
+        Ok(ret)
+    }
+
+    // The shoe to deal from, Spanish decks for Double Attack
+    pub fn composition(&self, default_decks: u32) -> ShoeComposition {
+        let decks = self.decks.unwrap_or(default_decks);
+        if self.rules.double_attack {
+            ShoeComposition::new_spanish_decks(decks)
+        } else {
+            ShoeComposition::new_decks(decks)
+        }
+    }
+}
+
+impl FromStr for RuleSet {
+    type Err = RulesParseError;
+    fn from_str(s: &str) -> Result<RuleSet, RulesParseError> {
+        RuleSet::parse(s)
+    }
+}
+
+impl fmt::Show for RuleSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.decks {
+            Some(d) => write!(f, "{}D {:?}", d, self.rules),
+            None => write!(f, "{:?}", self.rules),
+        }
     }
 }
 
@@ -157,6 +509,7 @@ mod tests {
     use cards::value;
     use cards::value::TEN;
     use shoe::randomshoe::new_infinite_shoe;
+    use rules::RuleSet;
 
     #[test]
     fn test_rules_after_split() {
@@ -191,4 +544,99 @@ mod tests {
         assert!(!rules.can_surrender(&hand));
         assert!(!rules.can_split(&hand));
     }
+
+    #[test]
+    fn test_rules_notation() {
+        let set = RuleSet::parse("6D H17 DAS RSA LS SPL3 BJ3:2").unwrap();
+        assert_eq!(Some(6), set.decks);
+        let mut expected = BJRules::new_complex(true, 3, true, 1, true, false, true);
+        assert!(set.rules == expected);
+        assert_eq!("6D H17 DAS RSA LS SPL3 DBL1 BJ3:2", format!("{:?}", set));
+
+        expected.set_blackjack_payout(1.2);
+        let s = format!("{:?}", expected);
+        assert_eq!("H17 DAS RSA LS SPL3 DBL1 BJ6:5", s);
+        assert!(RuleSet::parse(s.as_slice()).unwrap().rules == expected);
+        expected.set_blackjack_payout(1.2345);
+        let s = format!("{:?}", expected);
+        assert!(s.as_slice().ends_with(" BJ1.2345"));
+        assert!(RuleSet::parse(s.as_slice()).unwrap().rules == expected);
+        assert_eq!(1.25, RuleSet::parse("bj1.25").unwrap().rules.blackjack_payout());
+        assert!(RuleSet::parse("").unwrap().rules == BJRules::new());
+        assert!(RuleSet::parse("ndas, s17").unwrap().rules ==
+                BJRules::new_complex(false, 3, false, 1, false, false, false));
+
+        let set = RuleSet::parse("6D DE").unwrap();
+        expected = BJRules::new();
+        expected.set_double_exposure(true, false);
+        assert!(set.rules == expected);
+        assert_eq!("6D S17 DAS SPL3 DBL1 BJ1:1 DE", format!("{:?}", set));
+        let set = RuleSet::parse("BJ3:2 debj").unwrap();
+        assert!(set.rules.player_blackjack_wins_ties());
+        assert_eq!(1.5, set.rules.blackjack_payout());
+        assert!(RuleSet::parse(format!("{:?}", set).as_slice()).unwrap() == set);
+    }
+
+    #[test]
+    fn test_pontoon() {
+        let mut rules = BJRules::new();
+        rules.set_pontoon(true);
+        let mut shoe = new_infinite_shoe();
+        let mut hand = BJHand::new_from_deck(
+            &mut shoe, &vec![value::TWO, value::THREE]).unwrap();
+        // Twist or buy below 15
+        assert!(!rules.can_stand(&hand));
+        assert!(rules.can_double(&hand));
+        assert_eq!(2.0, rules.double_bet_factor(&hand));
+        // Bought a two
+        hand = BJHand::new_from_deck(
+            &mut shoe, &vec![value::TWO, value::THREE, value::TWO]).unwrap();
+        hand.add_double_count();
+        assert_eq!(1.5, rules.double_bet_factor(&hand));
+        assert!(rules.can_hit(&hand));
+        assert!(rules.can_double(&hand));
+        // Then twisted a three, so no more buying
+        hand = BJHand::new_from_deck(
+            &mut shoe, &vec![value::TWO, value::THREE, value::TWO, value::THREE]).unwrap();
+        hand.add_double_count();
+        assert!(!rules.can_double(&hand));
+        assert!(rules.can_hit(&hand));
+        hand = BJHand::new_from_deck(&mut shoe, &vec![value::TWO, value::THREE, value::TWO,
+                                                      value::THREE, value::TWO]).unwrap();
+        assert!(rules.is_automatic_win(&hand));
+        assert!(!rules.can_hit(&hand));
+        assert!(rules.can_stand(&hand));
+        assert!(!rules.can_surrender(&hand));
+        assert!(rules.dealer_wins_ties());
+
+        let set = RuleSet::parse("PON").unwrap();
+        assert!(set.rules == rules);
+        assert_eq!("S17 DAS SPL3 DBL3 BJ2:1 PON", format!("{:?}", set));
+        assert!(RuleSet::parse("PON LS").is_err());
+    }
+
+    #[test]
+    fn test_double_attack_notation() {
+        let set = RuleSet::parse("8D H17 DA").unwrap();
+        let mut expected = BJRules::new_complex(false, 3, true, 1, false, false, true);
+        expected.set_double_attack(true);
+        assert!(set.rules == expected);
+        assert_eq!("8D H17 DAS LS SPL3 DBL1 BJ1:1 DA", format!("{:?}", set));
+        assert!(RuleSet::parse(format!("{:?}", set).as_slice()).unwrap() == set);
+        assert_eq!("S17 DAS SPL3 DBL1 BJ1:1 DA", format!("{:?}", RuleSet::parse("DA NS").unwrap()));
+        assert_eq!(8 * 48, set.composition(6).len());
+        assert_eq!(6 * 52, RuleSet::parse("H17").unwrap().composition(6).len());
+    }
+
+    #[test]
+    fn test_rules_notation_errors() {
+        let e = RuleSet::parse("6D H17 XYZ").err().unwrap();
+        assert_eq!("XYZ", e.token.as_slice());
+        assert_eq!("bad rules token 'XYZ': unknown rule", format!("{:?}", e));
+        assert!(RuleSet::parse("H17 S17").is_err());
+        assert!(RuleSet::parse("BJ3").is_err());
+        assert!(RuleSet::parse("BJ1.x").is_err());
+        assert!(RuleSet::parse("SPLx").is_err());
+        assert!(RuleSet::parse("0D").is_err());
+    }
 }
@@ -0,0 +1,274 @@
+use std::str::FromStr;
+use cards::card::Card;
+use cards::parse::CardParseError;
+use cards::suit::SUITS;
+use cards::value::Value;
+use cards::value::TEN;
+use cards::value::ACE;
+use hand::SCORE_VALUES;
+use hand::score_for_value;
+use shoe::directshoe::DirectActualShoe;
+use shoe::randomshoe::GenericDirectShoe;
+use shoe::randomshoe::new_composition_shoe_seeded;
+use shoe::randomshoe::new_weighted_infinite_shoe_seeded;
+use shoe::shuffle::ShuffleStep;
+use shoe::shuffle::shuffle;
+use shoe::shuffle::seeded_rng;
+use shoe::shuffle::random_seed;
+
+/**
+  How many cards of each blackjack rank a shoe holds.  Ranks are indexed by
+  score - 1, and every ten valued card is counted as a TEN, so the shoes this
+  builds are faceless.
+  */
+#[derive(Copy, Clone, PartialEq)]
+pub struct ShoeComposition {
+    counts: [u32; 10],
+}
+
+impl ShoeComposition {
+    pub fn new_empty() -> ShoeComposition {
+        ShoeComposition {
+            counts: [0; 10],
+        }
+    }
+
+    pub fn new_decks(num_decks: u32) -> ShoeComposition {
+        let mut c = ShoeComposition {
+            counts: [4 * num_decks; 10],
+        };
+        c.counts[9] = 16 * num_decks;
+        c
+    }
+
+    // Spanish decks have no tens, leaving 12 ten valued cards in 48
+    pub fn new_spanish_decks(num_decks: u32) -> ShoeComposition {
+        let mut c = ShoeComposition::new_decks(num_decks);
+        c.counts[9] = 12 * num_decks;
+        c
+    }
+
+    pub fn new_from_counts(counts: [u32; 10]) -> ShoeComposition {
+        ShoeComposition {
+            counts: counts,
+        }
+    }
+
+    pub fn counts(&self) -> &[u32; 10] {
+        &self.counts
+    }
+
+    pub fn count(&self, v: &Value) -> u32 {
+        self.counts[score_for_value(v) as usize - 1]
+    }
+
+    pub fn set(&mut self, v: &Value, count: u32) -> &mut ShoeComposition {
+        self.counts[score_for_value(v) as usize - 1] = count;
+        self
+    }
+
+    pub fn add(&mut self, v: &Value, count: u32) -> &mut ShoeComposition {
+        self.counts[score_for_value(v) as usize - 1] += count;
+        self
+    }
+
+    pub fn remove(&mut self, v: &Value, count: u32) -> &mut ShoeComposition {
+        let index = score_for_value(v) as usize - 1;
+        assert!(self.counts[index] >= count);
+        self.counts[index] -= count;
+        self
+    }
+
+    // Keeps only the given values, removing every other card
+    pub fn retain_only(&mut self, values: &[Value]) -> &mut ShoeComposition {
+        for i in range(0, 10) {
+            if !values.iter().any(|v| score_for_value(v) as usize - 1 == i) {
+                self.counts[i] = 0;
+            }
+        }
+        self
+    }
+
+    pub fn len(&self) -> u32 {
+        let mut ret = 0;
+        for &c in self.counts.iter() {
+            ret += c;
+        }
+        ret
+    }
+
+    pub fn max_count(&self) -> u32 {
+        *self.counts.iter().max().unwrap()
+    }
+
+    pub fn density(&self, v: &Value) -> f64 {
+        match self.len() {
+            0 => 0.0,
+            l => self.count(v) as f64 / l as f64,
+        }
+    }
+
+    pub fn ten_density(&self) -> f64 {
+        self.density(&TEN)
+    }
+
+    pub fn ace_density(&self) -> f64 {
+        self.density(&ACE)
+    }
+
+    // Counts indexed like VALUES, with all tens on TEN
+    pub fn value_counts(&self) -> [u32; 13] {
+        let mut ret = [0; 13];
+        for i in range(0, 10) {
+            ret[SCORE_VALUES[i].index()] = self.counts[i];
+        }
+        ret
+    }
+
+    // Every card of the composition, in rank order
+    pub fn cards(&self) -> Vec<Card> {
+        let mut ret = Vec::with_capacity(self.len() as usize);
+        for i in range(0, 10) {
+            for j in range(0, self.counts[i]) {
+                ret.push(Card::new(SCORE_VALUES[i], SUITS[j as usize % SUITS.len()]));
+            }
+        }
+        ret
+    }
+
+    /**
+      Fills cards with a shuffled copy of this composition and returns a shoe
+      dealing from it.
+      */
+    pub fn direct_shoe<'a>(&self, cards: &'a mut Vec<Card>) -> DirectActualShoe<'a> {
+        self.direct_shoe_seeded(cards, random_seed())
+    }
+
+    pub fn direct_shoe_seeded<'a>(&self, cards: &'a mut Vec<Card>, seed: u32) -> DirectActualShoe<'a> {
+        cards.clear();
+        cards.push_all(self.cards().as_slice());
+        shuffle(cards, &[ShuffleStep::Perfect], &mut seeded_rng(seed));
+        DirectActualShoe {
+            initial_length: Some(cards.len() as u32),
+            // The deck hasher needs at least two possible counts per value
+            maximum_count_of_any_value: Some(if self.max_count() > 1 { self.max_count() } else { 1 }),
+            cards: cards,
+        }
+    }
+
+    pub fn generic_shoe<'a>(&self) -> GenericDirectShoe<'a> {
+        self.generic_shoe_seeded(random_seed())
+    }
+
+    pub fn generic_shoe_seeded<'a>(&self, seed: u32) -> GenericDirectShoe<'a> {
+        new_composition_shoe_seeded(&self.value_counts(), seed)
+    }
+
+    // An infinite shoe drawing each rank in proportion to this composition
+    pub fn infinite_shoe<'a>(&self) -> GenericDirectShoe<'a> {
+        self.infinite_shoe_seeded(random_seed())
+    }
+
+    pub fn infinite_shoe_seeded<'a>(&self, seed: u32) -> GenericDirectShoe<'a> {
+        new_weighted_infinite_shoe_seeded(self.value_counts(), seed)
+    }
+}
+
+/**
+  Either a number of full decks, like 6D, a number of Spanish decks, like
+  6S, or ten counts ace first and tens last: 4,4,4,4,4,4,4,4,4,16
+  */
+impl FromStr for ShoeComposition {
+    type Err = CardParseError;
+    fn from_str(s: &str) -> Result<ShoeComposition, CardParseError> {
+        let t = s.trim();
+        if t.ends_with("D") || t.ends_with("d") {
+            return match t[..t.len() - 1].parse::<u32>() {
+                Ok(n) if n > 0 => Ok(ShoeComposition::new_decks(n)),
+                _ => Err(CardParseError::new(s, "expected a number of decks, like 6D")),
+            };
+        }
+        if t.ends_with("S") || t.ends_with("s") {
+            return match t[..t.len() - 1].parse::<u32>() {
+                Ok(n) if n > 0 => Ok(ShoeComposition::new_spanish_decks(n)),
+                _ => Err(CardParseError::new(s, "expected a number of Spanish decks, like 6S")),
+            };
+        }
+        let mut c = ShoeComposition::new_empty();
+        let parts: Vec<&str> = t.split(',').collect();
+        if parts.len() != 10 {
+            return Err(CardParseError::new(s, "expected ten counts, ace first and tens last"));
+        }
+        for (i, p) in parts.iter().enumerate() {
+            match p.trim().parse::<u32>() {
+                Ok(n) => {
+                    c.set(&SCORE_VALUES[i], n);
+                }
+                Err(_) => return Err(CardParseError::new(*p, "expected a count of cards")),
+            }
+        }
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shoe::composition::ShoeComposition;
+    use shoe::shoe::DirectShoe;
+    use cards::value::{ACE, TEN, FIVE, JACK};
+
+    #[test]
+    fn test_composition_builders() {
+        let mut c = ShoeComposition::new_decks(6);
+        c.remove(&TEN, 8);
+        assert_eq!(6 * 52 - 8, c.len());
+        assert_eq!(96 - 8, c.count(&TEN));
+        assert_eq!(c.count(&TEN), c.count(&JACK));
+
+        let mut cards = Vec::new();
+        let direct = c.direct_shoe(&mut cards);
+        assert_eq!(6 * 52 - 8, direct.len());
+        assert_eq!(96 - 8, direct.count(&TEN));
+        assert_eq!(0, direct.count(&JACK));
+
+        let mut generic = c.generic_shoe();
+        assert_eq!(6 * 52 - 8, generic.len());
+        assert_eq!(24, generic.count(&FIVE));
+        let mut popped = 0;
+        while generic.pop().is_some() {
+            popped += 1;
+        }
+        assert_eq!(6 * 52 - 8, popped);
+    }
+
+    #[test]
+    fn test_aces_and_tens() {
+        let mut c = ShoeComposition::new_decks(1);
+        c.retain_only(&[ACE, TEN]);
+        assert_eq!(20, c.len());
+        assert_eq!(0.8, c.ten_density());
+        assert_eq!(0.2, c.ace_density());
+
+        let mut shoe = c.infinite_shoe();
+        for _ in range(0, 100) {
+            let card = shoe.pop().unwrap();
+            assert!(card.value() == &ACE || card.value() == &TEN);
+        }
+        assert_eq!(20, shoe.len());
+    }
+
+    #[test]
+    fn test_parse_composition() {
+        assert!("2D".parse::<ShoeComposition>().unwrap() == ShoeComposition::new_decks(2));
+        let c = "4,4,4,4,4,4,4,4,4,12".parse::<ShoeComposition>().unwrap();
+        assert_eq!(48, c.len());
+        assert_eq!(12, c.count(&TEN));
+        assert!("4,4,4".parse::<ShoeComposition>().is_err());
+        assert!("4,4,4,4,4,4,4,x,4,12".parse::<ShoeComposition>().is_err());
+        assert!("0D".parse::<ShoeComposition>().is_err());
+        let spanish = "2S".parse::<ShoeComposition>().unwrap();
+        assert!(spanish == ShoeComposition::new_spanish_decks(2));
+        assert_eq!(96, spanish.len());
+        assert_eq!(24, spanish.count(&TEN));
+    }
+}
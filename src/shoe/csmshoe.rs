@@ -0,0 +1,173 @@
+use std::rand::Rng;
+use std::rand::XorShiftRng;
+use cards::card::Card;
+use cards::value::Value;
+use cards::value::VALUES;
+use shoe::deck::cards_in_deck;
+use shoe::shoe::DirectShoe;
+use shoe::shoe::ShoeEvent;
+use shoe::shuffle::seeded_rng;
+use shoe::shuffle::random_seed;
+
+/**
+  A continuous shuffling machine.  Cards are dealt in order from a reservoir
+  that the machine keeps topped up with random cards.  Discards go back into
+  the machine delay_rounds rounds after they were played.
+  */
+pub struct ContinuousShuffleShoe {
+    machine: Vec<Card>,
+    // Next card to deal is last
+    reservoir: Vec<Card>,
+    reservoir_size: usize,
+    in_play: Vec<Card>,
+    // Discards of each round still waiting to go back in, oldest first
+    delayed: Vec<Vec<Card>>,
+    delay_rounds: u32,
+    total: u32,
+    maximum_count_of_any_value: u32,
+    rng: XorShiftRng,
+}
+
+impl ContinuousShuffleShoe {
+    pub fn new(cards: Vec<Card>, reservoir_size: usize, delay_rounds: u32) -> ContinuousShuffleShoe {
+        ContinuousShuffleShoe::new_seeded(cards, reservoir_size, delay_rounds, random_seed())
+    }
+
+    pub fn new_seeded(cards: Vec<Card>, reservoir_size: usize, delay_rounds: u32,
+                      seed: u32) -> ContinuousShuffleShoe {
+        let mut maximum_count_of_any_value = 1;
+        for v in VALUES.iter() {
+            let c = cards.iter().filter(|c| c.value() == v).count() as u32;
+            if c > maximum_count_of_any_value {
+                maximum_count_of_any_value = c;
+            }
+        }
+        let mut ret = ContinuousShuffleShoe {
+            total: cards.len() as u32,
+            maximum_count_of_any_value: maximum_count_of_any_value,
+            machine: cards,
+            reservoir: Vec::with_capacity(reservoir_size),
+            reservoir_size: reservoir_size,
+            in_play: Vec::new(),
+            delayed: Vec::new(),
+            delay_rounds: delay_rounds,
+            rng: seeded_rng(seed),
+        };
+        ret.fill_reservoir();
+        ret
+    }
+
+    pub fn new_decks(num_decks: u32, reservoir_size: usize,
+                     delay_rounds: u32) -> ContinuousShuffleShoe {
+        ContinuousShuffleShoe::new(cards_in_deck(num_decks), reservoir_size, delay_rounds)
+    }
+
+    fn fill_reservoir(&mut self) {
+        while self.reservoir.len() < self.reservoir_size && self.machine.len() > 0 {
+            let i = self.rng.gen_range(0, self.machine.len());
+            let c = self.machine.swap_remove(i);
+            self.reservoir.insert(0, c);
+        }
+    }
+
+    // Cards out of play but not yet back in the machine
+    pub fn waiting_cards(&self) -> usize {
+        self.delayed.iter().fold(0, |sum, d| sum + d.len())
+    }
+}
+
+impl DirectShoe for ContinuousShuffleShoe {
+    fn pop(&mut self) -> Option<Card> {
+        if self.reservoir.len() == 0 {
+            self.fill_reservoir();
+        }
+        let r = self.reservoir.pop();
+        match r {
+            Some(c) => self.in_play.push(c),
+            None => (),
+        };
+        // A reservoir of 0 cards deals straight from the machine
+        self.fill_reservoir();
+        return r;
+    }
+    fn len(&self) -> usize {
+        self.machine.len() + self.reservoir.len()
+    }
+    fn count(&self, v: &Value) -> u32 {
+        self.machine.iter().chain(self.reservoir.iter())
+            .filter(|c| c.value().index() == v.index()).count() as u32
+    }
+    fn remove(&mut self, v: &Value) -> Option<Card> {
+        let machine_index = self.machine.iter().position(|c| c.value().index() == v.index());
+        let reservoir_index = self.reservoir.iter().position(|c| c.value().index() == v.index());
+        let c = match (machine_index, reservoir_index) {
+            (Some(i), _) => self.machine.swap_remove(i),
+            (None, Some(i)) => self.reservoir.remove(i),
+            (None, None) => return None,
+        };
+        self.in_play.push(c);
+        Some(c)
+    }
+    fn insert(&mut self, v: &Card) {
+        let index = self.in_play.iter().rposition(|c| c == v);
+        match index {
+            Some(i) => {
+                self.in_play.remove(i);
+            }
+            None => {}
+        }
+        self.machine.push(*v);
+    }
+    fn initial_length(&self) -> Option<u32> {
+        Some(self.total)
+    }
+    fn maximum_count_of_any_value(&self) -> Option<u32> {
+        Some(self.maximum_count_of_any_value)
+    }
+    fn end_round(&mut self) -> Option<ShoeEvent> {
+        let round = self.in_play.clone();
+        self.in_play.clear();
+        self.delayed.push(round);
+        let mut returned = Vec::new();
+        while self.delayed.len() as u32 > self.delay_rounds {
+            returned.push_all(self.delayed.remove(0).as_slice());
+        }
+        if returned.len() == 0 {
+            return None;
+        }
+        self.machine.push_all(returned.as_slice());
+        self.fill_reservoir();
+        Some(ShoeEvent::Returned(returned))
+    }
+}
+
+#[test]
+fn test_csm_returns_discards() {
+    let mut shoe = ContinuousShuffleShoe::new_decks(1, 10, 1);
+    assert_eq!(52, shoe.len());
+    for _ in range(0, 5) {
+        shoe.pop().unwrap();
+    }
+    // Still waiting a round before going back in
+    assert!(shoe.end_round().is_none());
+    assert_eq!(47, shoe.len());
+    assert_eq!(5, shoe.waiting_cards());
+    for _ in range(0, 6) {
+        shoe.pop().unwrap();
+    }
+    match shoe.end_round() {
+        Some(ShoeEvent::Returned(cards)) => assert_eq!(5, cards.len()),
+        _ => panic!("Expected the first round's cards back"),
+    }
+    assert_eq!(46, shoe.len());
+    assert_eq!(6, shoe.waiting_cards());
+}
+
+#[test]
+fn test_csm_single_deck() {
+    use shoe::shoe::test_single_deck;
+    let mut shoe = ContinuousShuffleShoe::new_decks(1, 10, 0);
+    test_single_deck(&mut shoe);
+    assert!(shoe.end_round().is_some());
+    assert_eq!(52, shoe.len());
+}
@@ -0,0 +1,193 @@
+use std::rand::XorShiftRng;
+use cards::card::Card;
+use cards::value::Value;
+use cards::value::VALUES;
+use shoe::deck::cards_in_deck;
+use shoe::shoe::DirectShoe;
+use shoe::shoe::ShoeEvent;
+use shoe::shuffle::ShuffleStep;
+use shoe::shuffle::shuffle;
+use shoe::shuffle::seeded_rng;
+use shoe::shuffle::random_seed;
+
+/**
+  A shoe with a cut card.  Once the cut card comes out the current round is
+  finished from the same shoe, and everything is reshuffled at end_round().
+  */
+pub struct CutCardShoe {
+    cards: Vec<Card>,
+    // Cards dealt this round
+    in_play: Vec<Card>,
+    // Cards from earlier rounds since the last shuffle
+    discards: Vec<Card>,
+    // How many cards are dealt before the cut card comes out
+    cut_card_position: usize,
+    total: u32,
+    maximum_count_of_any_value: u32,
+    shuffles: u32,
+    procedure: Vec<ShuffleStep>,
+    rng: XorShiftRng,
+}
+
+impl CutCardShoe {
+    /**
+      penetration is the fraction of the shoe dealt before the cut card, so
+      0.75 cuts off the last quarter.
+      */
+    pub fn new(cards: Vec<Card>, penetration: f64) -> CutCardShoe {
+        CutCardShoe::new_seeded(cards, penetration, random_seed())
+    }
+
+    pub fn new_seeded(cards: Vec<Card>, penetration: f64, seed: u32) -> CutCardShoe {
+        assert!(penetration > 0.0 && penetration <= 1.0);
+        let mut maximum_count_of_any_value = 1;
+        for v in VALUES.iter() {
+            let c = cards.iter().filter(|c| c.value() == v).count() as u32;
+            if c > maximum_count_of_any_value {
+                maximum_count_of_any_value = c;
+            }
+        }
+        let mut ret = CutCardShoe {
+            cut_card_position: (cards.len() as f64 * penetration) as usize,
+            total: cards.len() as u32,
+            maximum_count_of_any_value: maximum_count_of_any_value,
+            cards: cards,
+            in_play: Vec::new(),
+            discards: Vec::new(),
+            shuffles: 0,
+            procedure: vec![ShuffleStep::Perfect],
+            rng: seeded_rng(seed),
+        };
+        ret.shuffle();
+        ret
+    }
+
+    pub fn new_decks(num_decks: u32, penetration: f64) -> CutCardShoe {
+        CutCardShoe::new(cards_in_deck(num_decks), penetration)
+    }
+
+    // How the dealer shuffles from now on.  The default is a perfect shuffle
+    pub fn set_shuffle_procedure(&mut self, steps: Vec<ShuffleStep>) {
+        self.procedure = steps;
+    }
+
+    // Puts every card not in play back in the shoe and shuffles it
+    pub fn shuffle(&mut self) {
+        self.cards.push_all(self.discards.as_slice());
+        self.discards.clear();
+        shuffle(&mut self.cards, self.procedure.as_slice(), &mut self.rng);
+        self.shuffles += 1;
+    }
+
+    pub fn cards_dealt(&self) -> usize {
+        self.total as usize - self.cards.len()
+    }
+
+    pub fn is_past_cut_card(&self) -> bool {
+        self.cards_dealt() >= self.cut_card_position
+    }
+
+    pub fn shuffles(&self) -> u32 {
+        self.shuffles
+    }
+
+    pub fn penetration(&self) -> f64 {
+        self.cut_card_position as f64 / self.total as f64
+    }
+}
+
+impl DirectShoe for CutCardShoe {
+    fn pop(&mut self) -> Option<Card> {
+        if self.cards.len() == 0 {
+            // Ran out mid round: the discards get shuffled and dealing goes
+            // on.  There's no event for this; a tracker sees the shoe grow
+            self.shuffle();
+        }
+        match self.cards.pop() {
+            Some(c) => {
+                self.in_play.push(c);
+                Some(c)
+            }
+            None => None,
+        }
+    }
+    fn len(&self) -> usize {
+        self.cards.len()
+    }
+    fn count(&self, v: &Value) -> u32 {
+        self.cards.iter().filter(|c| c.value().index() == v.index()).count() as u32
+    }
+    fn remove(&mut self, v: &Value) -> Option<Card> {
+        let index = self.cards.iter().rposition(|c| c.value().index() == v.index());
+        match index {
+            Some(i) => {
+                let c = self.cards.remove(i);
+                self.in_play.push(c);
+                Some(c)
+            }
+            None => None,
+        }
+    }
+    fn insert(&mut self, v: &Card) {
+        let index = self.in_play.iter().rposition(|c| c == v);
+        match index {
+            Some(i) => {
+                self.in_play.remove(i);
+            }
+            None => {}
+        }
+        self.cards.push(*v);
+    }
+    fn initial_length(&self) -> Option<u32> {
+        Some(self.total)
+    }
+    fn maximum_count_of_any_value(&self) -> Option<u32> {
+        Some(self.maximum_count_of_any_value)
+    }
+    fn end_round(&mut self) -> Option<ShoeEvent> {
+        self.discards.push_all(self.in_play.as_slice());
+        self.in_play.clear();
+        if self.is_past_cut_card() {
+            self.shuffle();
+            Some(ShoeEvent::Shuffled)
+        } else {
+            None
+        }
+    }
+}
+
+#[test]
+fn test_cut_card_shoe() {
+    let mut shoe = CutCardShoe::new_decks(1, 0.5);
+    assert_eq!(52, shoe.len());
+    assert_eq!(1, shoe.shuffles());
+    for _ in range(0, 20) {
+        shoe.pop().unwrap();
+    }
+    assert!(shoe.end_round() == None);
+    assert_eq!(32, shoe.len());
+    // The round that takes the cut card out is still finished
+    for _ in range(0, 10) {
+        shoe.pop().unwrap();
+    }
+    assert!(shoe.is_past_cut_card());
+    assert_eq!(22, shoe.len());
+    assert!(shoe.end_round() == Some(ShoeEvent::Shuffled));
+    assert_eq!(52, shoe.len());
+    assert_eq!(2, shoe.shuffles());
+}
+
+#[test]
+fn test_cut_card_shoe_keeps_cards_in_play() {
+    use shoe::shoe::test_single_deck;
+    let mut shoe = CutCardShoe::new_decks(1, 0.5);
+    test_single_deck(&mut shoe);
+    let mut shoe2 = CutCardShoe::new_decks(1, 0.75);
+    for _ in range(0, 52) {
+        shoe2.pop().unwrap();
+    }
+    // Every card is in play, so there's nothing to shuffle back in
+    assert!(shoe2.pop().is_none());
+    assert!(shoe2.end_round() == Some(ShoeEvent::Shuffled));
+    assert_eq!(52, shoe2.len());
+}
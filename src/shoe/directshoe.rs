@@ -2,6 +2,9 @@ use cards::value;
 use cards::value::Value;
 use cards::card::Card;
 use shoe::shoe::DirectShoe;
+use shoe::shuffle::ShuffleStep;
+use shoe::shuffle::shuffle;
+use std::rand::Rng;
 
 pub struct DirectActualShoe<'a> {
     pub cards: &'a mut Vec<Card>,
@@ -52,6 +55,10 @@ impl <'a> DirectActualShoe<'a> {
             cards: v,
         }
     }
+
+    pub fn shuffle<R: Rng>(&mut self, steps: &[ShuffleStep], rng: &mut R) {
+        shuffle(&mut *self.cards, steps, rng);
+    }
 }
 
 #[test]
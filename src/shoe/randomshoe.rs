@@ -8,7 +8,10 @@ use cards::value::KING;
 use cards::card::Card;
 use cards::suit::Suit;
 use cards::suit::SUITS;
-use std::rand;
+use std::rand::Rng;
+use std::rand::XorShiftRng;
+use shoe::shuffle::seeded_rng;
+use shoe::shuffle::random_seed;
 
 pub trait SuitPicker {
     fn suit(&mut self) -> Option<Suit>;
@@ -58,11 +61,13 @@ impl SuitPicker for CycleSuitPicker {
     }
 }
 
-struct RandomSuitPicker;
+struct RandomSuitPicker {
+    rng: XorShiftRng,
+}
 
 impl SuitPicker for RandomSuitPicker {
     fn suit(&mut self) -> Option<Suit> {
-        let suit_index = rand::random::<usize>() % SUITS.len();
+        let suit_index = self.rng.gen_range(0, SUITS.len());
         return Some(SUITS[suit_index as usize % 4]);
     }
     fn insert(&mut self, _: &Suit) {
@@ -80,11 +85,13 @@ impl SuitPicker for RandomSuitPicker {
 }
 
 
-struct RandomValuePicker;
+struct RandomValuePicker {
+    rng: XorShiftRng,
+}
 
 impl ValuePicker for RandomValuePicker {
     fn value(&mut self) -> Option<Value> {
-        let value_index = rand::random::<usize>() % VALUES.len();
+        let value_index = self.rng.gen_range(0, VALUES.len());
         Some(VALUES[value_index])
     }
     fn count(&self, _: &Value) -> u32 {
@@ -103,6 +110,59 @@ impl ValuePicker for RandomValuePicker {
     }
 }
 
+/**
+  An infinite deck where each value is drawn in proportion to its weight.
+  Weights are indexed like VALUES.
+  */
+pub struct WeightedValuePicker {
+    weights: [u32;13],
+    total: u32,
+    rng: XorShiftRng,
+}
+
+impl WeightedValuePicker {
+    pub fn new(weights: [u32;13], seed: u32) -> WeightedValuePicker {
+        let mut total = 0;
+        for &w in weights.iter() {
+            total += w;
+        }
+        assert!(total > 0);
+        WeightedValuePicker {
+            weights: weights,
+            total: total,
+            rng: seeded_rng(seed),
+        }
+    }
+}
+
+impl ValuePicker for WeightedValuePicker {
+    fn value(&mut self) -> Option<Value> {
+        let mut value_index = self.rng.gen_range(0, self.total);
+        for (i, &w) in self.weights.iter().enumerate() {
+            if value_index < w {
+                return Some(VALUES[i]);
+            }
+            value_index -= w;
+        }
+        panic!("Weights should always add up to the total.  Logic error!");
+    }
+    fn count(&self, v: &Value) -> u32 {
+        return self.weights[v.index()];
+    }
+    fn remove(&mut self, v: &Value) -> Option<Value> {
+        match self.weights[v.index()] {
+            0 => None,
+            _ => Some(VALUES[v.index()]),
+        }
+    }
+    fn insert(&mut self, _: &Value) {
+        // infinite deck.  Nothing done
+    }
+    fn len(&self) -> usize {
+        return self.total as usize;
+    }
+}
+
 struct IntCount {
     value: u32,
     counts: u32,
@@ -113,6 +173,7 @@ pub struct RandomItemPicker {
     non_zero_index_counts: Vec<usize>,
     indexed_value_counts: Vec<IntCount>,
     size: u32,
+    rng: XorShiftRng,
 }
 
 impl RandomItemPicker {
@@ -127,9 +188,33 @@ impl RandomItemPicker {
             non_zero_index_counts: non_zero_index_counts,
             indexed_value_counts: indexed_value_counts,
             size: initial_count_each * max_index,
+            rng: seeded_rng(random_seed()),
+        };
+    }
+
+    pub fn new_with_counts(counts: &[u32]) -> RandomItemPicker {
+        let mut non_zero_index_counts= Vec::new();
+        let mut indexed_value_counts= Vec::new();
+        let mut size = 0;
+        for (i, &c) in counts.iter().enumerate() {
+            if c > 0 {
+                non_zero_index_counts.push(i);
+            }
+            indexed_value_counts.push(IntCount{value: i as u32, counts: c});
+            size += c;
+        }
+        return RandomItemPicker {
+            non_zero_index_counts: non_zero_index_counts,
+            indexed_value_counts: indexed_value_counts,
+            size: size,
+            rng: seeded_rng(random_seed()),
         };
     }
 
+    pub fn reseed(&mut self, seed: u32) {
+        self.rng = seeded_rng(seed);
+    }
+
     pub fn get_index(&mut self, index_to_find: u32) -> Option<u32> {
         let mut current: u32 = 0;
         // The first time value_index <= current, we take the last value
@@ -157,7 +242,7 @@ impl RandomItemPicker {
             return None;
         }
         let value_to_ret = {
-            let value_index = rand::random::<u32>() % self.size;
+            let value_index = self.rng.gen_range(0, self.size);
             let value_count_index_to_consider = match self.get_index(value_index) {
                 Some(c) => c,
                 None => {
@@ -232,6 +317,12 @@ impl RandomDeckValuePicker {
     }
 }
 
+impl RandomDeckValuePicker {
+    pub fn reseed(&mut self, seed: u32) {
+        self.item_picker.reseed(seed);
+    }
+}
+
 impl ValuePicker for RandomDeckValuePicker {
     fn value(&mut self) -> Option<Value> {
         match self.item_picker.value() {
@@ -269,6 +360,25 @@ impl RandomDeckSuitPicker {
     }
 }
 
+impl RandomDeckSuitPicker {
+    // Spreads count cards as evenly as possible over the four suits
+    pub fn new_spread(count: u32) -> RandomDeckSuitPicker {
+        let mut counts = [count / 4; 4];
+        for i in range(0, (count % 4) as usize) {
+            counts[i] += 1;
+        }
+        return RandomDeckSuitPicker{
+            item_picker: RandomItemPicker::new_with_counts(&counts),
+        };
+    }
+}
+
+impl RandomDeckSuitPicker {
+    pub fn reseed(&mut self, seed: u32) {
+        self.item_picker.reseed(seed);
+    }
+}
+
 impl SuitPicker for RandomDeckSuitPicker {
     fn suit(&mut self) -> Option<Suit> {
         match self.item_picker.value() {
@@ -362,10 +472,18 @@ impl <'a>DirectShoe for GenericDirectShoe<'a> {
 }
 
 pub fn new_random_shoe<'a>(num_decks: u32) -> GenericDirectShoe<'a> {
-    let vp = RandomDeckValuePicker::new(num_decks);
+    new_random_shoe_seeded(num_decks, random_seed())
+}
+
+pub fn new_random_shoe_seeded<'a>(num_decks: u32, seed: u32) -> GenericDirectShoe<'a> {
+    let mut rng = seeded_rng(seed);
+    let mut vp = RandomDeckValuePicker::new(num_decks);
+    vp.reseed(rng.gen());
     let mut sp : Vec<Box<SuitPicker>> = Vec::new();
     for _ in range (0, 13) {
-        sp.push(Box::new(RandomDeckSuitPicker::new(num_decks)));
+        let mut p = RandomDeckSuitPicker::new(num_decks);
+        p.reseed(rng.gen());
+        sp.push(Box::new(p));
     }
     GenericDirectShoe {
         value_picker: Box::new(vp),
@@ -376,24 +494,24 @@ pub fn new_random_shoe<'a>(num_decks: u32) -> GenericDirectShoe<'a> {
 }
 
 pub fn new_faceless_random_shoe<'a>(num_decks: u32) -> GenericDirectShoe<'a> {
-    let vp = RandomDeckValuePicker::new_faceless(num_decks);
+    new_faceless_random_shoe_seeded(num_decks, random_seed())
+}
+
+pub fn new_faceless_random_shoe_seeded<'a>(num_decks: u32, seed: u32) -> GenericDirectShoe<'a> {
+    let mut rng = seeded_rng(seed);
+    let mut vp = RandomDeckValuePicker::new_faceless(num_decks);
+    vp.reseed(rng.gen());
     let mut sp : Vec<Box<SuitPicker>> = Vec::new();
     for i in range (0, 13) {
-         match VALUES[i] {
-            TEN =>  sp.push(Box::new(RandomDeckSuitPicker::new(4 * 4 * num_decks))),
-            JACK =>  sp.push(Box::new(RandomDeckSuitPicker::new(0))),
-            QUEEN =>  sp.push(Box::new(RandomDeckSuitPicker::new(0))),
-            KING =>  sp.push(Box::new(RandomDeckSuitPicker::new(0))),
-            _ => sp.push(Box::new(RandomDeckSuitPicker::new(num_decks))),
-        }
-
-/*         match VALUES[i] {
-            TEN =>  sp.push(Box::new(CycleSuitPicker::new()),
-            JACK =>  sp.push(Box::new(CycleSuitPicker::new()),
-            QUEEN =>  sp.push(Box::new(CycleSuitPicker::new()),
-            KING =>  sp.push(Box::new(CycleSuitPicker::new()),
-            _ => sp.push(Box::new(CycleSuitPicker::new()),
-        }*/
+        let mut p = match VALUES[i] {
+            TEN =>  RandomDeckSuitPicker::new(4 * 4 * num_decks),
+            JACK =>  RandomDeckSuitPicker::new(0),
+            QUEEN =>  RandomDeckSuitPicker::new(0),
+            KING =>  RandomDeckSuitPicker::new(0),
+            _ => RandomDeckSuitPicker::new(num_decks),
+        };
+        p.reseed(rng.gen());
+        sp.push(Box::new(p));
     }
     GenericDirectShoe {
         value_picker: Box::new(vp),
@@ -403,19 +521,76 @@ pub fn new_faceless_random_shoe<'a>(num_decks: u32) -> GenericDirectShoe<'a> {
     }
 }
 
-pub fn new_infinite_shoe<'a>() -> GenericDirectShoe<'a> {
-    let vp = RandomValuePicker;
+/**
+  A finite shoe holding counts[i] cards of VALUES[i]
+  */
+pub fn new_composition_shoe<'a>(counts: &[u32;13]) -> GenericDirectShoe<'a> {
+    new_composition_shoe_seeded(counts, random_seed())
+}
+
+pub fn new_composition_shoe_seeded<'a>(counts: &[u32;13], seed: u32) -> GenericDirectShoe<'a> {
+    let mut rng = seeded_rng(seed);
+    let mut vp = RandomDeckValuePicker{
+        item_picker: RandomItemPicker::new_with_counts(counts),
+    };
+    vp.reseed(rng.gen());
     let mut sp : Vec<Box<SuitPicker>> = Vec::new();
-    for _ in range (0, 13) {
-        sp.push(Box::new(RandomSuitPicker));
+    let mut total = 0;
+    let mut max_count = 1;
+    for &c in counts.iter() {
+        let mut p = RandomDeckSuitPicker::new_spread(c);
+        p.reseed(rng.gen());
+        sp.push(Box::new(p));
+        total += c;
+        if c > max_count {
+            max_count = c;
+        }
     }
     GenericDirectShoe {
         value_picker: Box::new(vp),
         suit_pickers: sp.into_boxed_slice(),
+        initial_length: Some(total),
+        maximum_count_of_any_value: Some(max_count),
+    }
+}
+
+fn random_suit_pickers<'a>(rng: &mut XorShiftRng) -> Box<[Box<SuitPicker + 'a>]> {
+    let mut sp : Vec<Box<SuitPicker + 'a>> = Vec::new();
+    for _ in range (0, 13) {
+        sp.push(Box::new(RandomSuitPicker{rng: seeded_rng(rng.gen())}));
+    }
+    sp.into_boxed_slice()
+}
+
+/**
+  An infinite shoe drawing VALUES[i] in proportion to weights[i]
+  */
+pub fn new_weighted_infinite_shoe<'a>(weights: [u32;13]) -> GenericDirectShoe<'a> {
+    new_weighted_infinite_shoe_seeded(weights, random_seed())
+}
+
+pub fn new_weighted_infinite_shoe_seeded<'a>(weights: [u32;13], seed: u32) -> GenericDirectShoe<'a> {
+    let mut rng = seeded_rng(seed);
+    GenericDirectShoe {
+        value_picker: Box::new(WeightedValuePicker::new(weights, rng.gen())),
+        suit_pickers: random_suit_pickers(&mut rng),
+        initial_length: None,
+        maximum_count_of_any_value: None,
+    }
+}
+
+pub fn new_infinite_shoe<'a>() -> GenericDirectShoe<'a> {
+    new_infinite_shoe_seeded(random_seed())
+}
+
+pub fn new_infinite_shoe_seeded<'a>(seed: u32) -> GenericDirectShoe<'a> {
+    let mut rng = seeded_rng(seed);
+    GenericDirectShoe {
+        value_picker: Box::new(RandomValuePicker{rng: seeded_rng(rng.gen())}),
+        suit_pickers: random_suit_pickers(&mut rng),
         initial_length: None,
         maximum_count_of_any_value: None,
     }
-    //GenericDirectShoe::new(Box::new(vp, sp.into_boxed_slice())
 }
 
 #[test]
@@ -440,3 +615,16 @@ fn test_cycle_suit_picker() {
     assert_eq!(4, s.len());
 }
 
+#[test]
+fn test_seeded_shoes_repeat() {
+    let mut a = new_random_shoe_seeded(2, 42);
+    let mut b = new_random_shoe_seeded(2, 42);
+    while a.len() > 0 {
+        assert!(a.pop() == b.pop());
+    }
+    let mut c = new_infinite_shoe_seeded(5);
+    let mut d = new_infinite_shoe_seeded(5);
+    for _ in range(0, 100) {
+        assert!(c.pop() == d.pop());
+    }
+}
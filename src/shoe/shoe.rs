@@ -12,6 +12,18 @@ pub trait DirectShoe {
     fn initial_length(&self) -> Option<u32>;
     // No count means the deck has no initial length: is infinite
     fn maximum_count_of_any_value(&self) -> Option<u32>;
+    // Called once the cards of a round are all played.  Shoes that reshuffle
+    // between rounds say so here so anything counting cards can start over.
+    fn end_round(&mut self) -> Option<ShoeEvent> {
+        None
+    }
+}
+
+#[derive(PartialEq, Clone)]
+pub enum ShoeEvent {
+    Shuffled,
+    // Cards from earlier rounds that went back into the shoe
+    Returned(Vec<Card>),
 }
 
 pub fn fmt(d: &DirectShoe) -> String {
@@ -0,0 +1,192 @@
+use std::rand;
+use std::rand::Rng;
+use std::rand::SeedableRng;
+use std::rand::XorShiftRng;
+use cards::card::Card;
+
+/**
+  Every random shoe and picker draws from an XorShiftRng, so a whole
+  simulation can be replayed from a single seed.
+  */
+pub fn seeded_rng(seed: u32) -> XorShiftRng {
+    // XorShift can't be seeded with all zeros
+    SeedableRng::from_seed([seed, seed ^ 0x9E3779B9, 0x243F6A88, 0xB7E15163])
+}
+
+pub fn random_seed() -> u32 {
+    rand::random::<u32>()
+}
+
+/**
+  One step of a shuffle.  Cards are dealt from the end of the vector, so the
+  end is the top of the deck.
+  */
+#[derive(Copy, Clone)]
+pub enum ShuffleStep {
+    // A uniformly random Fisher-Yates shuffle
+    Perfect,
+    // A riffle.  0.0 is a model riffle, 1.0 drops whole packets and is just a cut
+    Riffle(f64),
+    // Strips this many packets off the top, reversing their order.  Stripping
+    // no packets leaves the deck as it is
+    Strip(u32),
+    Cut,
+    // Plugs this many cards from the bottom back somewhere into the deck
+    Plug(u32),
+}
+
+// A typical hand shuffle: riffle, riffle, strip, riffle and cut
+pub fn casino_shuffle() -> Vec<ShuffleStep> {
+    vec![ShuffleStep::Riffle(0.3), ShuffleStep::Riffle(0.3), ShuffleStep::Strip(5),
+         ShuffleStep::Riffle(0.3), ShuffleStep::Cut]
+}
+
+pub fn shuffle<R: Rng>(cards: &mut Vec<Card>, steps: &[ShuffleStep], rng: &mut R) {
+    for step in steps.iter() {
+        match *step {
+            ShuffleStep::Perfect => rng.shuffle(cards.as_mut_slice()),
+            ShuffleStep::Riffle(clumping) => riffle(cards, clumping, rng),
+            ShuffleStep::Strip(packets) => strip(cards, packets, rng),
+            ShuffleStep::Cut => cut(cards, rng),
+            ShuffleStep::Plug(plug_size) => plug(cards, plug_size, rng),
+        }
+    }
+}
+
+/**
+  Splits the deck near the middle and drops cards from either half in
+  proportion to what's left in it.  clumping is the extra chance of dropping
+  the next card from the same half as the last one.
+  */
+pub fn riffle<R: Rng>(cards: &mut Vec<Card>, clumping: f64, rng: &mut R) {
+    let mut split = 0;
+    for _ in range(0, cards.len()) {
+        if rng.gen::<bool>() {
+            split += 1;
+        }
+    }
+    let left = cards[..split].to_vec();
+    let right = cards[split..].to_vec();
+    cards.clear();
+    let (mut l, mut r) = (0, 0);
+    let mut last_from_left: Option<bool> = None;
+    while l < left.len() || r < right.len() {
+        let left_remaining = left.len() - l;
+        let right_remaining = right.len() - r;
+        let from_left = if left_remaining == 0 {
+            false
+        } else if right_remaining == 0 {
+            true
+        } else {
+            let mut odds_left = left_remaining as f64 / (left_remaining + right_remaining) as f64;
+            match last_from_left {
+                Some(true) => odds_left += (1.0 - odds_left) * clumping,
+                Some(false) => odds_left *= 1.0 - clumping,
+                None => {}
+            }
+            rng.gen::<f64>() < odds_left
+        };
+        if from_left {
+            cards.push(left[l]);
+            l += 1;
+        } else {
+            cards.push(right[r]);
+            r += 1;
+        }
+        last_from_left = Some(from_left);
+    }
+}
+
+pub fn strip<R: Rng>(cards: &mut Vec<Card>, packets: u32, rng: &mut R) {
+    if packets == 0 {
+        return;
+    }
+    let average = match cards.len() / packets as usize {
+        0 => 1,
+        a => a,
+    };
+    let mut remaining = cards.clone();
+    cards.clear();
+    while remaining.len() > 0 {
+        let mut size = rng.gen_range(average / 2 + 1, average + average / 2 + 1);
+        if size > remaining.len() {
+            size = remaining.len();
+        }
+        let start = remaining.len() - size;
+        // The first packet off the top ends up on the bottom
+        cards.push_all(&remaining[start..]);
+        remaining.truncate(start);
+    }
+}
+
+// Cuts somewhere in the middle half of the deck
+pub fn cut<R: Rng>(cards: &mut Vec<Card>, rng: &mut R) {
+    if cards.len() < 2 {
+        return;
+    }
+    let at = rng.gen_range(cards.len() / 4, cards.len() * 3 / 4 + 1);
+    let mut ret = cards[at..].to_vec();
+    ret.push_all(&cards[..at]);
+    *cards = ret;
+}
+
+pub fn plug<R: Rng>(cards: &mut Vec<Card>, plug_size: u32, rng: &mut R) {
+    let size = if plug_size as usize > cards.len() { cards.len() } else { plug_size as usize };
+    let plugged = cards[..size].to_vec();
+    let mut rest = cards[size..].to_vec();
+    let at = rng.gen_range(0, rest.len() + 1);
+    let tail = rest[at..].to_vec();
+    rest.truncate(at);
+    rest.push_all(plugged.as_slice());
+    rest.push_all(tail.as_slice());
+    *cards = rest;
+}
+
+#[cfg(test)]
+mod tests {
+    use shoe::shuffle::{seeded_rng, shuffle, riffle, strip, ShuffleStep, casino_shuffle};
+    use shoe::deck::cards_in_deck;
+    use cards::card::Card;
+
+    fn sorted(cards: &Vec<Card>) -> Vec<(usize, usize)> {
+        let mut ret: Vec<(usize, usize)> = cards.iter().map(
+            |c| (c.value().index(), c.suit().index())).collect();
+        ret.sort();
+        ret
+    }
+
+    #[test]
+    fn test_seeded_shuffles_repeat() {
+        let mut a = cards_in_deck(2);
+        let mut b = cards_in_deck(2);
+        shuffle(&mut a, casino_shuffle().as_slice(), &mut seeded_rng(7));
+        shuffle(&mut b, casino_shuffle().as_slice(), &mut seeded_rng(7));
+        assert!(a == b);
+        assert!(sorted(&a) == sorted(&cards_in_deck(2)));
+        shuffle(&mut b, &[ShuffleStep::Perfect, ShuffleStep::Plug(20)], &mut seeded_rng(8));
+        assert_eq!(104, b.len());
+        assert!(sorted(&a) == sorted(&b));
+    }
+
+    #[test]
+    fn test_full_clumping_riffle_is_a_cut() {
+        let original = cards_in_deck(1);
+        let mut cards = original.clone();
+        riffle(&mut cards, 1.0, &mut seeded_rng(3));
+        let at = cards.iter().position(|c| *c == original[0]).unwrap();
+        for i in range(0, 52) {
+            assert!(cards[(at + i) % 52] == original[i]);
+        }
+    }
+
+    #[test]
+    fn test_strip() {
+        let original = cards_in_deck(1);
+        let mut cards = original.clone();
+        strip(&mut cards, 0, &mut seeded_rng(4));
+        assert!(cards == original);
+        strip(&mut cards, 4, &mut seeded_rng(4));
+        assert!(cards != original);
+        assert!(sorted(&cards) == sorted(&original));
+    }
+}
@@ -0,0 +1,447 @@
+use std::num::Float;
+use action_calculator::ActionCalculator;
+use bjaction::BJAction;
+use cards::value::Value;
+use cards::value::{ACE, TEN};
+use counting::CountingSystem;
+use hand::SCORE_VALUES;
+use rules::BJRules;
+use shoe::shoe::DirectShoe;
+use shoe::composition::ShoeComposition;
+use shoe::randomshoe::new_random_shoe;
+use hand::score_for_value;
+
+// Remaining cards of each blackjack score, indexed by score - 1
+pub type Composition = [u32; 10];
+
+pub fn full_composition(num_decks: u32) -> Composition {
+    *ShoeComposition::new_decks(num_decks).counts()
+}
+
+pub fn composition_len(c: &Composition) -> u32 {
+    let mut ret = 0;
+    for &i in c.iter() {
+        ret += i;
+    }
+    ret
+}
+
+/**
+  The true count of a shoe that started as num_decks full decks and is now
+  down to the given composition.
+  */
+pub fn true_count(system: &CountingSystem, num_decks: u32, c: &Composition) -> f64 {
+    let full = full_composition(num_decks);
+    let mut running_count = 0.0;
+    for i in range(0, 10) {
+        running_count += (full[i] - c[i]) as f64 * system.tags()[i];
+    }
+    running_count / (composition_len(c) as f64 / 52.0)
+}
+
+// Of the ranks with tags of the given sign, the one with the most left
+fn pick_rank(system: &CountingSystem, full: &Composition, c: &Composition,
+             sign: i32) -> Option<usize> {
+    let mut best = None;
+    let mut best_ratio = 0.0;
+    for i in range(0, 10) {
+        let tag = system.tags()[i];
+        let matches_sign = match sign {
+            1 => tag > 0.0,
+            -1 => tag < 0.0,
+            _ => true,
+        };
+        if c[i] == 0 || !matches_sign {
+            continue;
+        }
+        let ratio = c[i] as f64 / full[i] as f64;
+        if best.is_none() || ratio > best_ratio {
+            best = Some(i);
+            best_ratio = ratio;
+        }
+    }
+    best
+}
+
+/**
+  Deals cards_dealt cards out of a full shoe so that what remains sits at the
+  requested true count.  Cards are removed as evenly as the count allows.
+  Returns None if the count can't be reached at that depth.
+  */
+pub fn deplete_to_true_count(system: &CountingSystem, num_decks: u32, cards_dealt: u32,
+                             target_true_count: f64) -> Option<Composition> {
+    let full = full_composition(num_decks);
+    if cards_dealt >= composition_len(&full) {
+        return None;
+    }
+    let decks_remaining = (composition_len(&full) - cards_dealt) as f64 / 52.0;
+    let target = target_true_count * decks_remaining;
+    let mut c = full;
+    let mut running_count = 0.0;
+    for _ in range(0, cards_dealt) {
+        let diff = target - running_count;
+        let sign = if diff >= 0.5 { 1 } else if diff <= -0.5 { -1 } else { 0 };
+        match pick_rank(system, &full, &c, sign) {
+            Some(i) => {
+                c[i] -= 1;
+                running_count += system.tags()[i];
+            }
+            None => return None,
+        }
+    }
+    if (target - running_count).abs() > 1.0 {
+        return None;
+    }
+    Some(c)
+}
+
+pub struct Depletion {
+    pub true_count: f64,
+    pub composition: Composition,
+}
+
+/**
+  One depletion for each integer true count in [min_true_count, max_true_count]
+  that can be reached after cards_dealt cards.
+  */
+pub fn true_count_depletions(system: &CountingSystem, num_decks: u32, cards_dealt: u32,
+                             min_true_count: i32, max_true_count: i32) -> Vec<Depletion> {
+    let mut ret = Vec::new();
+    for tc in range(min_true_count, max_true_count + 1) {
+        match deplete_to_true_count(system, num_decks, cards_dealt, tc as f64) {
+            Some(c) => ret.push(Depletion {
+                true_count: tc as f64,
+                composition: c,
+            }),
+            None => {}
+        }
+    }
+    ret
+}
+
+/**
+  Deals cards_dealt cards from randomly shuffled shoes.  The true count of each
+  sample is truncated toward zero, the way a player would bucket it.  Empty if
+  that leaves no cards, since there's no true count then.
+  */
+pub fn sampled_depletions(system: &CountingSystem, num_decks: u32, cards_dealt: u32,
+                          samples: u32) -> Vec<Depletion> {
+    if cards_dealt >= composition_len(&full_composition(num_decks)) {
+        return Vec::new();
+    }
+    let mut ret = Vec::with_capacity(samples as usize);
+    for _ in range(0, samples) {
+        let mut shoe = new_random_shoe(num_decks);
+        let mut c = full_composition(num_decks);
+        for _ in range(0, cards_dealt) {
+            match shoe.pop() {
+                Some(card) => c[score_for_value(card.value()) as usize - 1] -= 1,
+                None => break,
+            }
+        }
+        ret.push(Depletion {
+            true_count: true_count(system, num_decks, &c).trunc(),
+            composition: c,
+        });
+    }
+    ret
+}
+
+/**
+  Representative starting hands: hard 5 to 19 without pairs, soft 13 to 20 and
+  every pair.
+  */
+pub fn starting_hands() -> Vec<Vec<Value>> {
+    let mut ret = Vec::new();
+    for total in range(5, 20) {
+        if total <= 11 {
+            let low = (total - 1) / 2;
+            ret.push(vec![SCORE_VALUES[low - 1], SCORE_VALUES[total - low - 1]]);
+        } else {
+            ret.push(vec![TEN, SCORE_VALUES[total - 11]]);
+        }
+    }
+    for i in range(1, 9) {
+        ret.push(vec![ACE, SCORE_VALUES[i]]);
+    }
+    for i in range(1, 10) {
+        ret.push(vec![SCORE_VALUES[i], SCORE_VALUES[i]]);
+    }
+    ret.push(vec![ACE, ACE]);
+    ret
+}
+
+pub struct StrategyEntry {
+    pub true_count: f64,
+    pub player: Vec<Value>,
+    pub dealer_up: Value,
+    pub evs: Vec<(BJAction, f64)>,
+}
+
+impl StrategyEntry {
+    pub fn best_action(&self) -> Option<BJAction> {
+        best_of(&self.evs)
+    }
+}
+
+fn best_of(evs: &Vec<(BJAction, f64)>) -> Option<BJAction> {
+    best_with_ev(evs).map(|(a, _)| a)
+}
+
+// The action with the highest EV and its EV, the first one on a tie
+pub fn best_with_ev(evs: &Vec<(BJAction, f64)>) -> Option<(BJAction, f64)> {
+    let mut best: Option<(BJAction, f64)> = None;
+    for &(a, ev) in evs.iter() {
+        match best {
+            Some((_, b)) if b >= ev => {}
+            _ => best = Some((a, ev)),
+        }
+    }
+    best
+}
+
+/**
+  The EV of every action for each hand against each up card, at every depletion.
+  The calculator is shared by all hands of a depletion so its cache is reused.
+  */
+pub fn export_strategy(rules: &BJRules, depletions: &Vec<Depletion>,
+                       hands: &Vec<Vec<Value>>) -> Vec<StrategyEntry> {
+    let mut ret = Vec::new();
+    for d in depletions.iter() {
+        let mut cards = Vec::new();
+        let mut shoe = ShoeComposition::new_from_counts(d.composition).direct_shoe(&mut cards);
+        let mut calc = ActionCalculator::new(*rules, &mut shoe);
+        for up in SCORE_VALUES.iter() {
+            for player in hands.iter() {
+                match calc.action_values(player, up) {
+                    Some(evs) => ret.push(StrategyEntry {
+                        true_count: d.true_count,
+                        player: player.clone(),
+                        dealer_up: *up,
+                        evs: evs,
+                    }),
+                    None => {}
+                }
+            }
+        }
+    }
+    ret
+}
+
+pub struct IndexNumber {
+    pub player: Vec<Value>,
+    pub dealer_up: Value,
+    // Best play below the index
+    pub below: BJAction,
+    // Best play at or above the index
+    pub at_or_above: BJAction,
+    pub true_count: f64,
+}
+
+/**
+  Every count where the best play for a hand and up card changes.  Entries
+  sharing a count (sampled depletions) have their EVs averaged first.  Entries
+  whose count isn't a finite number are left out.
+  */
+pub fn index_numbers(entries: &Vec<StrategyEntry>) -> Vec<IndexNumber> {
+    let mut keys: Vec<(Vec<Value>, Value)> = Vec::new();
+    for e in entries.iter() {
+        if !keys.iter().any(|k| k.0 == e.player && k.1 == e.dealer_up) {
+            keys.push((e.player.clone(), e.dealer_up));
+        }
+    }
+    let mut ret = Vec::new();
+    for &(ref player, dealer_up) in keys.iter() {
+        // (true count, [(action, summed evs, samples with the action)])
+        let mut by_count: Vec<(f64, Vec<(BJAction, f64, u32)>)> = Vec::new();
+        for e in entries.iter().filter(|e| e.player == *player && e.dealer_up == dealer_up &&
+                                       e.true_count.is_finite()) {
+            let i = match by_count.iter().position(|b| b.0 == e.true_count) {
+                Some(i) => i,
+                None => {
+                    by_count.push((e.true_count, Vec::new()));
+                    by_count.len() - 1
+                }
+            };
+            let ref mut bucket = by_count[i];
+            for &(a, ev) in e.evs.iter() {
+                match bucket.1.iter().position(|x| x.0 == a) {
+                    Some(j) => {
+                        bucket.1[j].1 += ev;
+                        bucket.1[j].2 += 1;
+                    }
+                    None => bucket.1.push((a, ev, 1)),
+                }
+            }
+        }
+        by_count.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let mut previous: Option<BJAction> = None;
+        for &(tc, ref sums) in by_count.iter() {
+            let evs: Vec<(BJAction, f64)> = sums.iter().map(
+                |&(a, sum, samples)| (a, sum / samples as f64)).collect();
+            let best = match best_of(&evs) {
+                Some(b) => b,
+                None => continue,
+            };
+            match previous {
+                Some(p) if p != best => ret.push(IndexNumber {
+                    player: player.clone(),
+                    dealer_up: dealer_up,
+                    below: p,
+                    at_or_above: best,
+                    true_count: tc,
+                }),
+                _ => {}
+            }
+            previous = Some(best);
+        }
+    }
+    ret
+}
+
+/**
+  EV of an insurance bet against an ace up card for this composition,
+  ignoring the player's own cards.  The composition has to hold the ace and
+  a hole card.
+  */
+pub fn insurance_value(c: &Composition) -> f64 {
+    assert!(c[0] > 0 && composition_len(c) > 1);
+    let len = composition_len(c) - 1;
+    let tens = c[9] as f64;
+    (2.0 * tens - (len as f64 - tens)) / len as f64
+}
+
+// The lowest count at which insurance becomes a winning bet
+pub fn insurance_index(depletions: &Vec<Depletion>) -> Option<f64> {
+    let mut ret: Option<f64> = None;
+    for d in depletions.iter() {
+        if d.composition[0] == 0 || composition_len(&d.composition) < 2 ||
+            insurance_value(&d.composition) <= 0.0 {
+            continue;
+        }
+        match ret {
+            Some(tc) if tc <= d.true_count => {}
+            _ => ret = Some(d.true_count),
+        }
+    }
+    ret
+}
+
+fn hand_desc(values: &Vec<Value>) -> String {
+    let mut s = String::new();
+    for v in values.iter() {
+        s.push(v.char());
+    }
+    s
+}
+
+pub fn entries_to_csv(entries: &Vec<StrategyEntry>) -> String {
+    let mut s = String::from_str("true_count,player,dealer_up,action,ev,best\n");
+    for e in entries.iter() {
+        let best = e.best_action();
+        for &(a, ev) in e.evs.iter() {
+            s.push_str(format!("{},{},{},{:?},{},{}\n", e.true_count, hand_desc(&e.player),
+                               e.dealer_up.char(), a, ev, Some(a) == best).as_slice());
+        }
+    }
+    s
+}
+
+pub fn index_numbers_to_csv(indexes: &Vec<IndexNumber>) -> String {
+    let mut s = String::from_str("player,dealer_up,below,at_or_above,true_count\n");
+    for i in indexes.iter() {
+        s.push_str(format!("{},{},{:?},{:?},{}\n", hand_desc(&i.player), i.dealer_up.char(),
+                           i.below, i.at_or_above, i.true_count).as_slice());
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::Float;
+    use strategy_export::{StrategyEntry, deplete_to_true_count, composition_len, true_count};
+    use strategy_export::{starting_hands, index_numbers, true_count_depletions, insurance_index};
+    use strategy_export::sampled_depletions;
+    use counting::HI_LO;
+    use bjaction::BJAction::{HIT, STAND};
+    use cards::value::{TEN, SIX};
+
+    #[test]
+    fn test_deplete_to_true_count() {
+        for tc in range(-4, 5) {
+            let c = deplete_to_true_count(&HI_LO, 6, 156, tc as f64).unwrap();
+            assert_eq!(6 * 52 - 156, composition_len(&c));
+            assert!((true_count(&HI_LO, 6, &c) - tc as f64).abs() < 0.34);
+        }
+        // Not enough low cards in a single deck to reach this
+        assert!(deplete_to_true_count(&HI_LO, 1, 10, 40.0).is_none());
+    }
+
+    #[test]
+    fn test_starting_hands() {
+        let hands = starting_hands();
+        assert_eq!(15 + 8 + 10, hands.len());
+        for h in hands.iter() {
+            assert_eq!(2, h.len());
+        }
+    }
+
+    #[test]
+    fn test_index_numbers() {
+        let mut entries = Vec::new();
+        for tc in range(-2, 3) {
+            let stand_ev = if tc >= 1 { -0.5 } else { -0.6 };
+            entries.push(StrategyEntry {
+                true_count: tc as f64,
+                player: vec![TEN, SIX],
+                dealer_up: TEN,
+                evs: vec![(STAND, stand_ev), (HIT, -0.55)],
+            });
+        }
+        let indexes = index_numbers(&entries);
+        assert_eq!(1, indexes.len());
+        assert_eq!(1.0, indexes[0].true_count);
+        assert_eq!(HIT, indexes[0].below);
+        assert_eq!(STAND, indexes[0].at_or_above);
+    }
+
+    #[test]
+    fn test_index_numbers_averaged() {
+        let entry = |tc: f64, evs| StrategyEntry {
+            true_count: tc,
+            player: vec![TEN, SIX],
+            dealer_up: TEN,
+            evs: evs,
+        };
+        // Two samples at 0, only one of which could stand: summed, hitting
+        // would look worse than standing
+        let entries = vec![entry(-1.0, vec![(STAND, -0.6), (HIT, -0.55)]),
+                           entry(0.0, vec![(STAND, -0.6), (HIT, -0.55)]),
+                           entry(0.0, vec![(HIT, -0.55)]),
+                           entry(1.0, vec![(STAND, -0.5), (HIT, -0.55)])];
+        let indexes = index_numbers(&entries);
+        assert_eq!(1, indexes.len());
+        assert_eq!(1.0, indexes[0].true_count);
+        // A count that isn't a number has no place among the others
+        let mut entries = entries;
+        entries.push(entry(Float::nan(), vec![(STAND, 1.0), (HIT, -1.0)]));
+        assert_eq!(1, index_numbers(&entries).len());
+    }
+
+    #[test]
+    fn test_sampled_depletions() {
+        let depletions = sampled_depletions(&HI_LO, 1, 26, 3);
+        assert_eq!(3, depletions.len());
+        assert!(depletions.iter().all(|d| composition_len(&d.composition) == 26));
+        // Dealing the whole shoe leaves nothing to count
+        assert!(sampled_depletions(&HI_LO, 1, 52, 3).is_empty());
+    }
+
+    #[test]
+    fn test_insurance_index() {
+        let depletions = true_count_depletions(&HI_LO, 6, 156, -2, 5);
+        // Hi-lo insures somewhere near +3
+        let index = insurance_index(&depletions).unwrap();
+        assert!(index >= 2.0 && index <= 4.0);
+    }
+}
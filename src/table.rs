@@ -0,0 +1,195 @@
+use action_calculator::ActionCalculator;
+use bjaction::BJAction;
+use bjaction::BJAction::STAND;
+use history::Player;
+use round::{Round, RoundState, RoundError};
+use rules::BJRules;
+use shoe::shoe::DirectShoe;
+use strategy_export::best_with_ev;
+
+/**
+  Plays the best action for the cards it can't see, which leaves out the
+  cards of every seat already dealt, not just its own.  Insures when that's
+  worth more than nothing.
+  */
+pub struct CompositionPlayer;
+
+impl Player for CompositionPlayer {
+    fn insurance(&mut self, round: &Round) -> bool {
+        round.insurance_ev(round.insurance_seat().unwrap()) > 0.0
+    }
+
+    fn action(&mut self, round: &Round) -> BJAction {
+        let up = *round.dealer_up_card();
+        let mut hand = round.active_hand().unwrap().hand().clone();
+        let mut cards = Vec::new();
+        let mut shoe = round.unseen_composition().direct_shoe(&mut cards);
+        let mut calc = ActionCalculator::new(*round.rules(), &mut shoe);
+        let mut evs = Vec::new();
+        for a in round.legal_actions().iter() {
+            match calc.expected_value(&mut hand, &up, *a, true) {
+                Some(ev) => evs.push((*a, ev)),
+                None => {}
+            }
+        }
+        best_with_ev(&evs).map_or(STAND, |b| b.0)
+    }
+}
+
+// One seat's player and how it has done
+pub struct Seat<'a> {
+    player: Box<Player + 'a>,
+    wager: f64,
+    rounds: u32,
+    total: f64,
+    total_squares: f64,
+}
+
+impl <'a>Seat<'a> {
+    pub fn wager(&self) -> f64 {
+        self.wager
+    }
+
+    pub fn rounds(&self) -> u32 {
+        self.rounds
+    }
+
+    // Net units won over every round
+    pub fn total(&self) -> f64 {
+        self.total
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.rounds == 0 { 0.0 } else { self.total / self.rounds as f64 }
+    }
+
+    pub fn variance(&self) -> f64 {
+        if self.rounds == 0 {
+            return 0.0;
+        }
+        let mean = self.mean();
+        self.total_squares / self.rounds as f64 - mean * mean
+    }
+}
+
+/**
+  Seats sharing one shoe, each with its own player.  Every round deals all
+  the seats, and each one's decisions are made with the other seats' cards
+  on the table.  Like Round, this leaves calling end_round() on the shoe to
+  the caller.
+  */
+pub struct Table<'a> {
+    rules: BJRules,
+    seats: Vec<Seat<'a>>,
+}
+
+impl <'a>Table<'a> {
+    pub fn new(rules: BJRules) -> Table<'a> {
+        Table {
+            rules: rules,
+            seats: Vec::new(),
+        }
+    }
+
+    // Seats are played in the order they're added.  Returns the seat's index
+    pub fn add_seat(&mut self, player: Box<Player + 'a>, wager: f64) -> usize {
+        self.seats.push(Seat {
+            player: player,
+            wager: wager,
+            rounds: 0,
+            total: 0.0,
+            total_squares: 0.0,
+        });
+        self.seats.len() - 1
+    }
+
+    pub fn seats(&self) -> &Vec<Seat<'a>> {
+        &self.seats
+    }
+
+    // Plays a round and returns every seat's net result
+    pub fn play_round<'b>(&mut self, shoe: &'b mut (DirectShoe + 'b)) -> Result<Vec<f64>, RoundError> {
+        let wagers: Vec<f64> = self.seats.iter().map(|s| s.wager).collect();
+        let mut round = try!(Round::deal_seats(self.rules, shoe, wagers.as_slice()));
+        while round.state() == RoundState::Insurance {
+            let seat = round.insurance_seat().unwrap();
+            let take = self.seats[seat].player.insurance(&round);
+            try!(round.take_insurance(take));
+        }
+        while round.state() == RoundState::PlayerTurn {
+            let seat = round.active_seat().unwrap();
+            let a = self.seats[seat].player.action(&round);
+            try!(round.act(a));
+        }
+        let results: Vec<f64> = range(0, self.seats.len()).map(
+            |i| round.seat_result(i).unwrap()).collect();
+        for (seat, result) in self.seats.iter_mut().zip(results.iter()) {
+            seat.rounds += 1;
+            seat.total += *result;
+            seat.total_squares += *result * *result;
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use table::{Table, CompositionPlayer};
+    use bjaction::BJAction;
+    use bjaction::BJAction::{HIT, STAND};
+    use cards::value::{TWO, THREE, FOUR, FIVE, SIX, SEVEN, EIGHT, NINE, TEN};
+    use history::Player;
+    use round::Round;
+    use round::tests::stacked_shoe;
+    use rules::BJRules;
+
+    // Hits below 17
+    struct SimplePlayer;
+
+    impl Player for SimplePlayer {
+        fn insurance(&mut self, _: &Round) -> bool {
+            false
+        }
+        fn action(&mut self, round: &Round) -> BJAction {
+            if round.active_hand().unwrap().hand().score() < 17 { HIT } else { STAND }
+        }
+    }
+
+    #[test]
+    fn test_table_round() {
+        // Seat 0 gets T and 6, seat 1 9 and 9, the dealer 8 and T, then seat
+        // 0 draws the 2
+        let mut cards = Vec::new();
+        let mut shoe = stacked_shoe(&mut cards, &[TEN, NINE, EIGHT, SIX, NINE, TEN, TWO, FIVE]);
+        let mut table = Table::new(BJRules::new());
+        table.add_seat(Box::new(SimplePlayer), 1.0);
+        table.add_seat(Box::new(SimplePlayer), 2.0);
+        // 18 and 18 against the dealer's 18
+        assert_eq!(vec![0.0, 0.0], table.play_round(&mut shoe).unwrap());
+        assert_eq!(1, table.seats()[1].rounds());
+        assert_eq!(0.0, table.seats()[0].variance());
+    }
+
+    #[test]
+    fn test_other_seats_cards() {
+        let mut cards = Vec::new();
+        let mut shoe = stacked_shoe(&mut cards, &[TEN, TEN, SEVEN, SIX, NINE, TEN, TWO, THREE, FOUR]);
+        let mut round = Round::deal_seats(BJRules::new(), &mut shoe, &[1.0, 1.0]).unwrap();
+        assert_eq!(2, round.seats());
+        assert_eq!(Some(0), round.active_seat());
+        // The rest of the shoe and the hole card
+        assert_eq!(4, round.unseen_composition().len());
+        round.act(HIT).unwrap();
+        round.act(STAND).unwrap();
+        // Seat 1 has seen the 2 seat 0 drew, and stands on 19
+        assert_eq!(Some(1), round.active_seat());
+        assert_eq!(3, round.unseen_composition().len());
+        assert_eq!(0, round.unseen_composition().count(&TWO));
+        assert!(CompositionPlayer.action(&round) == STAND);
+        round.act(STAND).unwrap();
+        // 18 and 19 against 17
+        assert_eq!(Some(1.0), round.seat_result(0));
+        assert_eq!(Some(1.0), round.seat_result(1));
+        assert_eq!(Some(2.0), round.net_result());
+    }
+}